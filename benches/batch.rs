@@ -0,0 +1,38 @@
+use oof::{BatchVerifier, HashAlg, Oof};
+use std::time::Instant;
+
+const DEPTH: u32 = 32;
+const PROOFS: u128 = 1000;
+
+fn proofs(alg: HashAlg) -> Vec<(Oof, [u8; 32])> {
+    (0..PROOFS)
+        .map(|i| {
+            let mut oof = Oof::with_hasher(&[(1 << DEPTH) + i * 7], &[[i as u8; 32]], alg);
+            oof.set_zero_padding(DEPTH);
+            let root = oof.compute_root().unwrap();
+            (oof, root)
+        })
+        .collect()
+}
+
+fn bench(name: &str, alg: HashAlg) {
+    let mut individual = proofs(alg);
+    let start = Instant::now();
+    for (oof, root) in individual.iter_mut() {
+        assert!(oof.verify(root).unwrap());
+    }
+    println!("verify/{}/{} proofs: {:?}", name, PROOFS, start.elapsed());
+
+    let mut batched = proofs(alg);
+    let mut batch = BatchVerifier::new();
+    let start = Instant::now();
+    for (oof, root) in batched.iter_mut() {
+        assert!(batch.verify(oof, root).unwrap());
+    }
+    println!("batch/{}/{} proofs: {:?}", name, PROOFS, start.elapsed());
+}
+
+fn main() {
+    bench("sha256", HashAlg::Sha256);
+    bench("keccak256", HashAlg::Keccak256);
+}
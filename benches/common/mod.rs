@@ -0,0 +1,53 @@
+// Each bench uses only some of these helpers.
+#![allow(dead_code)]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_TOTAL: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_TOTAL.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+/// Returns the result of `f` along with the bytes it left allocated.
+pub fn measure<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let ret = f();
+    (ret, ALLOCATED.load(Ordering::Relaxed) - before)
+}
+
+/// Allocations made while running a closure, including ones since freed.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Allocations {
+    pub count: usize,
+    pub bytes: usize,
+}
+
+/// Returns the result of `f` along with every allocation it made.
+pub fn count_allocations<T>(f: impl FnOnce() -> T) -> (T, Allocations) {
+    let (count, bytes) = (
+        ALLOCATIONS.load(Ordering::Relaxed),
+        ALLOCATED_TOTAL.load(Ordering::Relaxed),
+    );
+    let ret = f();
+    let allocations = Allocations {
+        count: ALLOCATIONS.load(Ordering::Relaxed) - count,
+        bytes: ALLOCATED_TOTAL.load(Ordering::Relaxed) - bytes,
+    };
+    (ret, allocations)
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use common::{measure, Counting};
+use oof::{Oof, OofCow};
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const VERSIONS: usize = 100;
+
+fn base() -> Oof {
+    let first = 1u128 << 12;
+    let keys: Vec<u128> = (first..first * 2).collect();
+    let values: Vec<[u8; 32]> = keys.iter().map(|k| [*k as u8; 32]).collect();
+    let mut oof = Oof::new(&keys, &values);
+    oof.root().unwrap();
+    oof
+}
+
+fn main() {
+    let oof = base();
+    let first = 1u128 << 12;
+
+    for &diverging in [VERSIONS, VERSIONS / 10].iter() {
+        let (_clones, cloned) = measure(|| {
+            let mut versions = vec![oof.clone()];
+            for i in 1..VERSIONS {
+                let mut next = versions[i - 1].clone();
+                if i % (VERSIONS / diverging) == 0 {
+                    next.set(first + i as u128, [0u8; 32]);
+                }
+                versions.push(next);
+            }
+            versions
+        });
+
+        let (_cows, shared) = measure(|| {
+            let mut versions = vec![OofCow::new(oof.clone())];
+            for i in 1..VERSIONS {
+                let mut next = versions[i - 1].clone();
+                if i % (VERSIONS / diverging) == 0 {
+                    next.set(first + i as u128, [0u8; 32]);
+                }
+                versions.push(next);
+            }
+            versions
+        });
+
+        println!(
+            "{} versions, {} diverging: Oof {} bytes, OofCow {} bytes",
+            VERSIONS, diverging, cloned, shared
+        );
+    }
+}
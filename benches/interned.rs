@@ -0,0 +1,40 @@
+mod common;
+
+use common::{measure, Counting};
+use oof::{Interned, Oof};
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn main() {
+    // A sparse proof: one branch per leaf, with most siblings being zero hashes.
+    let depth = 32;
+    let first = 1u128 << depth;
+    let mut keys = Vec::new();
+    let mut values = Vec::new();
+
+    for i in 0..1024u128 {
+        let mut key = first + i * 4096;
+        keys.push(key);
+        values.push([i as u8; 32]);
+
+        let mut zero = [0u8; 32];
+        while key > 1 {
+            keys.push(key ^ 1);
+            values.push(zero);
+            zero[0] = zero[0].wrapping_add(1);
+            key /= 2;
+        }
+    }
+
+    let (oof, plain) = measure(|| Oof::new(&keys, &values));
+    let (interned, compact) = measure(|| Interned::new(&keys, &values));
+
+    println!(
+        "{} entries, {} distinct values: Oof {} bytes, Interned {} bytes",
+        oof.len(),
+        interned.distinct_values(),
+        plain,
+        compact
+    );
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use common::{count_allocations, Allocations, Counting};
+use oof::Oof;
+use std::time::{Duration, Instant};
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const ITERATIONS: u32 = 10;
+
+fn bench(name: &str, oof: &Oof) {
+    let mut elapsed = Duration::default();
+    let mut total = Allocations::default();
+
+    for _ in 0..ITERATIONS {
+        let mut oof = oof.clone();
+        let start = Instant::now();
+        let (_, allocations) = count_allocations(|| oof.root().unwrap());
+        elapsed += start.elapsed();
+        total.count += allocations.count;
+        total.bytes += allocations.bytes;
+    }
+
+    let iterations = ITERATIONS as usize;
+    println!(
+        "root/{} ({} entries): {:?}, {} allocations of {} bytes per iteration",
+        name,
+        oof.len(),
+        elapsed / ITERATIONS,
+        total.count / iterations,
+        total.bytes / iterations
+    );
+}
+
+fn full(depth: u32) -> Oof {
+    let first = 1u128 << depth;
+    let keys: Vec<u128> = (first..first * 2).collect();
+    let values: Vec<[u8; 32]> = keys.iter().map(|k| [*k as u8; 32]).collect();
+    Oof::new(&keys, &values)
+}
+
+fn sparse(depth: u32, leaves: u128) -> Oof {
+    let first = 1u128 << depth;
+    let stride = first / leaves;
+    let mut keys = Vec::new();
+
+    for i in 0..leaves {
+        let mut key = first + i * stride;
+        keys.push(key);
+        while key > 1 {
+            keys.push(key ^ 1);
+            key /= 2;
+        }
+    }
+
+    keys.sort();
+    keys.dedup();
+
+    let values: Vec<[u8; 32]> = keys.iter().map(|k| [*k as u8; 32]).collect();
+    Oof::new(&keys, &values)
+}
+
+fn main() {
+    bench("full-16", &full(16));
+    bench("full-18", &full(18));
+    bench("sparse-64", &sparse(64, 4096));
+}
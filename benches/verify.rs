@@ -0,0 +1,54 @@
+mod common;
+
+use common::{count_allocations, Counting};
+use oof::Oof;
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const ITERATIONS: u32 = 10_000;
+
+fn main() {
+    // A single branch proof for a leaf of a depth 32 tree.
+    let mut key = (1u128 << 32) + 12345;
+    let mut keys = vec![key];
+    while key > 1 {
+        keys.push(key ^ 1);
+        key /= 2;
+    }
+
+    let values: Vec<[u8; 32]> = keys.iter().map(|k| [*k as u8; 32]).collect();
+    let oof = Oof::new(&keys, &values);
+    let root = oof.compute_root().unwrap();
+
+    let start = Instant::now();
+    let (_, allocations) = count_allocations(|| {
+        for _ in 0..ITERATIONS {
+            assert_eq!(oof.compute_root(), Ok(root));
+        }
+    });
+    println!(
+        "compute_root/{} iterations: {:?}, {} allocations of {} bytes per iteration",
+        ITERATIONS,
+        start.elapsed(),
+        allocations.count / ITERATIONS as usize,
+        allocations.bytes / ITERATIONS as usize
+    );
+
+    let mut scratch = BTreeMap::new();
+    let start = Instant::now();
+    let (_, allocations) = count_allocations(|| {
+        for _ in 0..ITERATIONS {
+            assert_eq!(oof.compute_root_into(&mut scratch), Ok(root));
+        }
+    });
+    println!(
+        "compute_root_into/{} iterations: {:?}, {} allocations of {} bytes per iteration",
+        ITERATIONS,
+        start.elapsed(),
+        allocations.count / ITERATIONS as usize,
+        allocations.bytes / ITERATIONS as usize
+    );
+}
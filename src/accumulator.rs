@@ -0,0 +1,174 @@
+use crate::{Error, Oof, K, V};
+
+use alloc::collections::BTreeSet;
+use bonsai::sibling;
+
+/// Builds up a proof one node at a time while tracking whether the root can be
+/// derived yet. Each insert costs at most one step per level of the tree.
+#[derive(Clone, Debug, Default)]
+pub struct Accumulator {
+    oof: Oof,
+    available: BTreeSet<K>,
+}
+
+impl Accumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Result<(), Error> {
+        if key == 0 {
+            return Err(Error::InvalidIndex(key));
+        }
+
+        self.oof.set(key, value);
+
+        // A node is only available once nothing below it is still waiting on a
+        // sibling. The insert evicts the stale parent, so every ancestor is
+        // re-checked against its children rather than trusted.
+        let mut ready = !self.has_descendants(key)
+            || (self.available.contains(&(key * 2)) && self.available.contains(&(key * 2 + 1)));
+
+        let mut key = key;
+        loop {
+            if ready {
+                self.available.insert(key);
+            } else {
+                self.available.remove(&key);
+            }
+
+            if key == 1 {
+                break;
+            }
+
+            ready = ready && self.available.contains(&sibling(key));
+            key /= 2;
+        }
+
+        Ok(())
+    }
+
+    pub fn is_root_ready(&self) -> bool {
+        self.available.contains(&1)
+    }
+
+    pub fn finish(mut self) -> Result<V, Error> {
+        self.oof.root()
+    }
+
+    fn has_descendants(&self, key: K) -> bool {
+        let (mut first, mut last) = (key, key);
+
+        while let (Some(f), Some(l)) = (first.checked_mul(2), last.checked_mul(2)) {
+            first = f;
+            last = l + 1;
+
+            if self.oof.map.range(first..=last).next().is_some() {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_value(n: u8) -> V {
+        let mut tmp = [0u8; 32];
+        tmp[0] = n;
+        tmp
+    }
+
+    #[test]
+    fn ready_on_last_node() {
+        let keys: alloc::vec::Vec<K> = (16..32).collect();
+        let values: alloc::vec::Vec<V> = (16..32).map(build_value).collect();
+        let full = Oof::new(&keys, &values);
+        let proof = full.prove(&[18, 29]).unwrap();
+
+        let mut nodes: alloc::vec::Vec<(K, V)> = proof.map.iter().map(|(k, v)| (*k, *v)).collect();
+
+        let mut seed: u64 = 0x9e37_79b9_7f4a_7c15;
+        for i in (1..nodes.len()).rev() {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            nodes.swap(i, (seed % (i as u64 + 1)) as usize);
+        }
+
+        let mut accumulator = Accumulator::new();
+        let (last, rest) = nodes.split_last().unwrap();
+
+        for (key, value) in rest {
+            accumulator.insert(*key, *value).unwrap();
+            assert!(!accumulator.is_root_ready());
+        }
+
+        accumulator.insert(last.0, last.1).unwrap();
+        assert!(accumulator.is_root_ready());
+        assert_eq!(accumulator.finish(), full.compute_root());
+    }
+
+    #[test]
+    fn not_ready() {
+        let mut accumulator = Accumulator::new();
+        accumulator.insert(2, build_value(2)).unwrap();
+        accumulator.insert(6, build_value(6)).unwrap();
+
+        assert!(!accumulator.is_root_ready());
+        assert_eq!(accumulator.finish(), Err(Error::EntryNotFound(7)));
+    }
+
+    #[test]
+    fn insert_zero() {
+        let mut accumulator = Accumulator::new();
+
+        assert_eq!(
+            accumulator.insert(0, build_value(0)),
+            Err(Error::InvalidIndex(0))
+        );
+        assert!(!accumulator.is_root_ready());
+        assert_eq!(accumulator.finish(), Err(Error::EmptyProof));
+    }
+
+    #[test]
+    fn insert_below_present_node() {
+        let mut accumulator = Accumulator::new();
+        accumulator.insert(2, build_value(2)).unwrap();
+        accumulator.insert(3, build_value(3)).unwrap();
+        assert!(accumulator.is_root_ready());
+
+        accumulator.insert(4, build_value(4)).unwrap();
+        assert!(!accumulator.is_root_ready());
+        assert_eq!(accumulator.clone().finish(), Err(Error::EntryNotFound(5)));
+
+        accumulator.insert(5, build_value(5)).unwrap();
+        assert!(accumulator.is_root_ready());
+        assert!(accumulator.finish().is_ok());
+
+        let mut accumulator = Accumulator::new();
+        accumulator.insert(1, build_value(1)).unwrap();
+        assert!(accumulator.is_root_ready());
+
+        accumulator.insert(2, build_value(2)).unwrap();
+        assert!(!accumulator.is_root_ready());
+        assert_eq!(accumulator.finish(), Err(Error::EntryNotFound(3)));
+    }
+
+    #[test]
+    fn insert_above_present_node() {
+        let mut accumulator = Accumulator::new();
+        accumulator.insert(4, build_value(4)).unwrap();
+        accumulator.insert(2, build_value(2)).unwrap();
+        accumulator.insert(3, build_value(3)).unwrap();
+        assert!(!accumulator.is_root_ready());
+        assert_eq!(accumulator.clone().finish(), Err(Error::EntryNotFound(5)));
+
+        accumulator.insert(5, build_value(5)).unwrap();
+        assert!(accumulator.is_root_ready());
+        assert!(accumulator.finish().is_ok());
+    }
+}
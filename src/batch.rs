@@ -0,0 +1,96 @@
+use crate::hash::{HashAlg, ZERO_HASHES};
+use crate::{zero_hashes, Error, Oof, V};
+
+use alloc::vec::Vec;
+
+/// Verifies many zero-padded proofs, sharing one table of zero hashes per hash
+/// function between them instead of rebuilding it for every proof.
+///
+/// SHA-256 proofs padded to fewer than 64 levels already borrow the static
+/// `ZERO_HASHES`, so batching only saves work for deeper SHA-256 padding and
+/// for other hash functions.
+#[derive(Clone, Debug, Default)]
+pub struct BatchVerifier {
+    zero_hashes: Vec<(HashAlg, Vec<V>)>,
+}
+
+impl BatchVerifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn verify(&mut self, oof: &mut Oof, root: &V) -> Result<bool, Error> {
+        let depth = match oof.padding {
+            Some(depth) => depth,
+            None => return oof.verify(root),
+        };
+
+        let alg = oof.hasher;
+        if alg == HashAlg::Sha256 && (depth as usize) < ZERO_HASHES.len() {
+            return oof.verify(root);
+        }
+
+        let index = match self.zero_hashes.iter().position(|(a, _)| *a == alg) {
+            Some(index) => index,
+            None => {
+                self.zero_hashes.push((alg, Vec::new()));
+                self.zero_hashes.len() - 1
+            }
+        };
+
+        let zeros = &mut self.zero_hashes[index].1;
+        if zeros.len() <= depth as usize {
+            *zeros = zero_hashes(|l, r| alg.hash(l, r), depth);
+        }
+
+        Ok(oof.root_with_zero_hashes(zeros)? == *root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_value(n: u8) -> V {
+        let mut tmp = [0u8; 32];
+        tmp[0] = n;
+        tmp
+    }
+
+    #[test]
+    fn shared_cache() {
+        let mut batch = BatchVerifier::new();
+
+        for depth in [4, 2, 8].iter() {
+            let mut oof = Oof::with_hasher(&[1 << depth], &[build_value(1)], HashAlg::Keccak256);
+            oof.set_zero_padding(*depth);
+
+            let root = oof.compute_root().unwrap();
+            assert_eq!(batch.verify(&mut oof, &root), Ok(true));
+            assert_eq!(batch.verify(&mut oof, &build_value(0)), Ok(false));
+        }
+
+        let keccak = |l: &V, r: &V| HashAlg::Keccak256.hash(l, r);
+        assert_eq!(
+            batch.zero_hashes,
+            [(HashAlg::Keccak256, zero_hashes(keccak, 8))]
+        );
+    }
+
+    #[test]
+    fn static_zero_hashes() {
+        let mut batch = BatchVerifier::new();
+
+        let mut shallow = Oof::new(&[1 << 8], &[build_value(1)]);
+        shallow.set_zero_padding(8);
+        let root = shallow.compute_root().unwrap();
+        assert_eq!(batch.verify(&mut shallow, &root), Ok(true));
+        assert!(batch.zero_hashes.is_empty());
+
+        let mut deep = Oof::new(&[1 << 70], &[build_value(1)]);
+        deep.set_zero_padding(70);
+        let root = deep.compute_root().unwrap();
+        assert_eq!(batch.verify(&mut deep, &root), Ok(true));
+        assert_eq!(batch.zero_hashes[0].1.len(), 71);
+    }
+}
@@ -0,0 +1,88 @@
+use crate::{Error, Oof, K, V};
+
+use alloc::rc::Rc;
+
+/// A proof whose clones share storage until one of them is mutated, at which
+/// point the mutated clone copies the whole proof.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OofCow {
+    inner: Rc<Oof>,
+}
+
+impl OofCow {
+    pub fn new(oof: Oof) -> Self {
+        Self {
+            inner: Rc::new(oof),
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.inner.get(key)
+    }
+
+    pub fn set(&mut self, key: K, value: V) -> Option<V> {
+        Rc::make_mut(&mut self.inner).set(key, value)
+    }
+
+    /// Derives the root. While storage is shared the derivation runs on a
+    /// temporary copy, so the derived internal nodes aren't kept.
+    pub fn root(&mut self) -> Result<V, Error> {
+        if Rc::strong_count(&self.inner) > 1 {
+            return self.inner.compute_root();
+        }
+
+        Rc::make_mut(&mut self.inner).root()
+    }
+
+    /// Returns whether both versions still share the same storage.
+    pub fn shares_storage(&self, other: &OofCow) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    pub fn into_inner(self) -> Oof {
+        Rc::try_unwrap(self.inner).unwrap_or_else(|inner| (*inner).clone())
+    }
+}
+
+impl From<Oof> for OofCow {
+    fn from(oof: Oof) -> Self {
+        Self::new(oof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_value(n: u8) -> V {
+        let mut tmp = [0u8; 32];
+        tmp[0] = n;
+        tmp
+    }
+
+    #[test]
+    fn copy_on_write() {
+        let oof = Oof::new(&[2, 3], &[build_value(2), build_value(3)]);
+        let mut first = OofCow::new(oof.clone());
+        let mut second = first.clone();
+
+        assert!(first.shares_storage(&second));
+        assert_eq!(second.root(), oof.compute_root());
+        assert!(first.shares_storage(&second));
+
+        second.set(3, build_value(4));
+        assert!(!first.shares_storage(&second));
+        assert_eq!(first.get(&3), Some(&build_value(3)));
+        assert_eq!(second.get(&3), Some(&build_value(4)));
+
+        assert_eq!(first.root(), oof.compute_root());
+        assert_eq!(
+            second.root(),
+            Ok(crate::hash::hash(&build_value(2), &build_value(4)))
+        );
+        assert_eq!(
+            first.into_inner().cached_root(),
+            oof.compute_root().ok().as_ref()
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use crate::K;
+
+use bonsai::log2;
+
+/// Returns the generalized index of `index` within the subtree rooted at
+/// `root`, or `None` if either index is zero or the result overflows.
+pub fn concat_gindex(root: K, index: K) -> Option<K> {
+    if root == 0 {
+        return None;
+    }
+
+    let depth = depth(index)?;
+    let offset = index - (1 << depth);
+
+    root.checked_mul(1u128.checked_shl(depth)?)?
+        .checked_add(offset)
+}
+
+/// Returns the depth of `index` below the root, or `None` for the invalid
+/// index zero.
+pub fn depth(index: K) -> Option<u32> {
+    if index == 0 {
+        return None;
+    }
+
+    Some(K::BITS - 1 - index.leading_zeros())
+}
+
+/// Returns the generalized index of the leaf at `offset` in a tree of `depth`.
+pub fn leaf_gindex(offset: u64, depth: u32) -> K {
+    debug_assert!((offset as K) < 1 << depth, "offset out of range for depth");
+    (1 << depth) + offset as K
+}
+
+/// Returns whether `index` is `root` or one of its descendants.
+pub fn is_in_subtree(root: K, index: K) -> bool {
+    if root == 0 || index < root {
+        return false;
+    }
+
+    let diff = log2(index) - log2(root);
+    index >> diff == root
+}
+
+/// Returns the lowest common ancestor of two non-zero generalized indices.
+pub fn common_ancestor(a: K, b: K) -> K {
+    let (mut a, mut b) = (a, b);
+
+    while log2(a) > log2(b) {
+        a /= 2;
+    }
+
+    while log2(b) > log2(a) {
+        b /= 2;
+    }
+
+    while a != b {
+        a /= 2;
+        b /= 2;
+    }
+
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concat() {
+        assert_eq!(concat_gindex(1, 6), Some(6));
+        assert_eq!(concat_gindex(5, 1), Some(5));
+        assert_eq!(concat_gindex(5, 2), Some(10));
+        assert_eq!(concat_gindex(5, 3), Some(11));
+        assert_eq!(concat_gindex(3, 6), Some(14));
+        assert_eq!(concat_gindex(0, 6), None);
+        assert_eq!(concat_gindex(1 << 100, 1 << 30), None);
+    }
+
+    #[test]
+    fn ancestor() {
+        assert_eq!(common_ancestor(8, 11), 2);
+        assert_eq!(common_ancestor(8, 9), 4);
+        assert_eq!(common_ancestor(8, 8), 8);
+        assert_eq!(common_ancestor(2, 11), 2);
+        assert_eq!(common_ancestor(11, 3), 1);
+        assert_eq!(common_ancestor(1, 1 << 100), 1);
+    }
+
+    #[test]
+    fn depths() {
+        assert_eq!(depth(0), None);
+        assert_eq!(depth(1), Some(0));
+        assert_eq!(depth(2), Some(1));
+        assert_eq!(depth(3), Some(1));
+        for index in 4..8 {
+            assert_eq!(depth(index), Some(2));
+        }
+        assert_eq!(depth(1 << 100), Some(100));
+        assert_eq!(depth((1 << 101) - 1), Some(100));
+        assert_eq!(depth(K::MAX), Some(127));
+    }
+
+    #[test]
+    fn leaf() {
+        assert_eq!(leaf_gindex(0, 0), 1);
+        assert_eq!(leaf_gindex(0, 3), 8);
+        assert_eq!(leaf_gindex(3, 3), 11);
+        assert_eq!(leaf_gindex(7, 3), 15);
+        assert_eq!(leaf_gindex(u64::MAX, 64), (1 << 65) - 1);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn leaf_out_of_range() {
+        leaf_gindex(8, 3);
+    }
+
+    #[test]
+    fn subtree() {
+        assert!(is_in_subtree(1, 1));
+        assert!(is_in_subtree(1, 12));
+        assert!(is_in_subtree(2, 9));
+        assert!(is_in_subtree(5, 11));
+        assert!(is_in_subtree(5, 23));
+        assert!(!is_in_subtree(5, 12));
+        assert!(!is_in_subtree(5, 2));
+        assert!(!is_in_subtree(3, 2));
+        assert!(!is_in_subtree(0, 2));
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{Error, Oof, K, V};
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use bonsai::expand;
+
+/// A proof that stores each distinct value once and maps keys to indices into
+/// that table. Useful for proofs dominated by repeated values, such as the zero
+/// hashes of sparse trees.
+///
+/// Values are never removed from the table, even once no key refers to them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Interned {
+    map: BTreeMap<K, u32>,
+    values: Vec<V>,
+    indices: BTreeMap<V, u32>,
+}
+
+impl Interned {
+    pub fn new(keys: &[K], values: &[V]) -> Self {
+        let mut ret = Self::default();
+
+        for (key, value) in keys.iter().zip(values.iter()) {
+            let index = ret.intern(value);
+            ret.map.insert(*key, index);
+        }
+
+        ret
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns the number of distinct values stored.
+    pub fn distinct_values(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.map.get(key).map(|index| &self.values[*index as usize])
+    }
+
+    pub fn set(&mut self, key: K, value: V) -> Option<V> {
+        let (_, _, parent) = expand(key);
+        self.map.remove(&parent);
+
+        let index = self.intern(&value);
+        self.map
+            .insert(key, index)
+            .map(|old| self.values[old as usize])
+    }
+
+    /// Derives the root by expanding into a regular `Oof`, so the memory
+    /// savings don't extend to the derivation itself.
+    pub fn root(&self) -> Result<V, Error> {
+        Oof::from(self).root()
+    }
+
+    fn intern(&mut self, value: &V) -> u32 {
+        match self.indices.get(value) {
+            Some(index) => *index,
+            None => {
+                let index = self.values.len() as u32;
+                self.values.push(*value);
+                self.indices.insert(*value, index);
+                index
+            }
+        }
+    }
+}
+
+impl From<&Interned> for Oof {
+    fn from(interned: &Interned) -> Self {
+        let map = interned
+            .map
+            .iter()
+            .map(|(key, index)| (*key, interned.values[*index as usize]))
+            .collect();
+
+        Oof::from_map(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_value(n: u8) -> V {
+        let mut tmp = [0u8; 32];
+        tmp[0] = n;
+        tmp
+    }
+
+    #[test]
+    fn dedup_values() {
+        let keys = [4, 5, 6, 7];
+        let values = [build_value(1), [0u8; 32], [0u8; 32], [0u8; 32]];
+        let mut interned = Oof::new_interned(&keys, &values);
+
+        assert_eq!(interned.len(), 4);
+        assert_eq!(interned.distinct_values(), 2);
+        assert_eq!(interned.get(&5), Some(&[0u8; 32]));
+        assert_eq!(interned.get(&4), Some(&build_value(1)));
+        assert_eq!(interned.get(&8), None);
+        assert_eq!(interned.root(), Oof::new(&keys, &values).root());
+
+        assert_eq!(interned.set(4, [0u8; 32]), Some(build_value(1)));
+        assert_eq!(interned.get(&4), Some(&[0u8; 32]));
+        assert_eq!(interned.distinct_values(), 2);
+    }
+}
@@ -0,0 +1,270 @@
+use crate::{Endianness, Error, Map, Oof, K, V};
+
+use alloc::vec::Vec;
+use core::convert::Infallible;
+use core::mem::size_of;
+
+/// A source of bytes, for streaming a proof in without `std::io`.
+///
+/// This and `Write` are the crate's own rather than those of `core2::io` or
+/// `embedded-io`, so the `io` feature adds no dependencies. They follow the
+/// same `read` and `write` contracts, with the error as an associated type as
+/// in `embedded-io`, so either crate's types can forward to them directly.
+pub trait Read {
+    type Error;
+
+    /// Reads into `buf`, returning how many bytes were read. Returning 0 for a
+    /// non-empty `buf` means the stream has ended.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error>;
+}
+
+/// A sink of bytes, for streaming a proof out without `std::io`.
+pub trait Write {
+    type Error;
+
+    /// Writes some prefix of `buf`, returning how many bytes were taken.
+    /// Returning 0 for a non-empty `buf` means no more bytes can be written.
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error>;
+}
+
+impl Read for &[u8] {
+    type Error = Infallible;
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let len = buf.len().min(self.len());
+        let (head, tail) = self.split_at(len);
+        buf[..len].copy_from_slice(head);
+        *self = tail;
+        Ok(len)
+    }
+}
+
+impl Write for Vec<u8> {
+    type Error = Infallible;
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum IoError<E> {
+    /// The underlying reader or writer failed.
+    Io(E),
+    /// The writer stopped accepting bytes before the whole proof was written.
+    WriteZero,
+    /// The bytes read don't form a valid proof.
+    Proof(Error),
+}
+
+impl Oof {
+    /// Writes the proof to `writer` in the format of `to_bytes`.
+    pub fn write_to_writer<W: Write>(&self, writer: &mut W) -> Result<(), IoError<W::Error>> {
+        write_all(writer, &(self.map.len() as u32).to_le_bytes())?;
+
+        for key in self.map.keys() {
+            write_all(writer, &key.to_le_bytes())?;
+        }
+
+        for value in self.map.values() {
+            write_all(writer, value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a proof in the format of `to_bytes` from `reader`, consuming
+    /// exactly the bytes that make it up.
+    pub fn read_from_reader<R: Read>(reader: &mut R) -> Result<Self, IoError<R::Error>> {
+        let mut count = [0u8; 4];
+        read_exact(reader, &mut count, 0)?;
+        let count = u32::from_le_bytes(count) as usize;
+
+        // The count is untrusted, so the keys are only stored as they arrive
+        // rather than reserved for up front, and lengths derived from it
+        // saturate instead of overflowing.
+        let keys_len = count.saturating_mul(size_of::<K>());
+        let mut keys = Vec::new();
+        for i in 0..count {
+            let mut key = [0u8; size_of::<K>()];
+            read_exact(reader, &mut key, 4)
+                .map_err(|e| at(e, i.saturating_mul(key.len()), keys_len))?;
+            keys.push(Endianness::Little.key_from_bytes(key));
+        }
+
+        let values_offset = keys_len.saturating_add(4);
+        let values_len = count.saturating_mul(size_of::<V>());
+        let mut map = Map::new();
+        for (i, key) in keys.into_iter().enumerate() {
+            let mut value = [0u8; 32];
+            read_exact(reader, &mut value, values_offset)
+                .map_err(|e| at(e, i.saturating_mul(value.len()), values_len))?;
+            map.insert(key, value);
+        }
+
+        Ok(Self::from_map(map))
+    }
+}
+
+fn write_all<W: Write>(writer: &mut W, mut buf: &[u8]) -> Result<(), IoError<W::Error>> {
+    while !buf.is_empty() {
+        match writer.write(buf).map_err(IoError::Io)? {
+            0 => return Err(IoError::WriteZero),
+            n => buf = &buf[n..],
+        }
+    }
+
+    Ok(())
+}
+
+/// Fills `buf` from `reader`. A stream that ends early is reported as malformed
+/// input of the region at `offset`, with `buf` taken to be the whole region.
+fn read_exact<R: Read>(
+    reader: &mut R,
+    buf: &mut [u8],
+    offset: usize,
+) -> Result<(), IoError<R::Error>> {
+    let mut filled = 0;
+
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]).map_err(IoError::Io)? {
+            0 => {
+                return Err(IoError::Proof(Error::MalformedInput {
+                    offset,
+                    expected: buf.len(),
+                    actual: filled,
+                }))
+            }
+            n => filled += n,
+        }
+    }
+
+    Ok(())
+}
+
+/// Places a short read of one item at `position` within a region of `len`.
+fn at<E>(error: IoError<E>, position: usize, len: usize) -> IoError<E> {
+    match error {
+        IoError::Proof(Error::MalformedInput { offset, actual, .. }) => {
+            IoError::Proof(Error::MalformedInput {
+                offset,
+                expected: len,
+                actual: position.saturating_add(actual),
+            })
+        }
+        e => e,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_value(n: u8) -> V {
+        let mut tmp = [0u8; 32];
+        tmp[0] = n;
+        tmp
+    }
+
+    /// Hands out at most one byte per call.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        type Error = Infallible;
+
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let len = buf.len().min(1);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    /// Accepts at most `capacity` bytes in total.
+    struct Bounded {
+        written: Vec<u8>,
+        capacity: usize,
+    }
+
+    impl Write for Bounded {
+        type Error = Infallible;
+
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            let len = buf.len().min(self.capacity - self.written.len());
+            self.written.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let oof = Oof::new(
+            &[2, 6, 1 << 100],
+            &[build_value(2), build_value(6), build_value(7)],
+        );
+
+        let mut bytes = Vec::new();
+        oof.write_to_writer(&mut bytes).unwrap();
+        assert_eq!(bytes, oof.to_bytes());
+
+        assert_eq!(
+            Oof::read_from_reader(&mut bytes.as_slice()),
+            Ok(oof.clone())
+        );
+        assert_eq!(Oof::read_from_reader(&mut Trickle(&bytes)), Ok(oof.clone()));
+
+        let mut stream = bytes.clone();
+        stream.extend_from_slice(&[0xff; 3]);
+        let mut reader = stream.as_slice();
+        assert_eq!(Oof::read_from_reader(&mut reader), Ok(oof));
+        assert_eq!(reader, [0xff; 3]);
+    }
+
+    #[test]
+    fn short_read() {
+        let oof = Oof::new(&[2, 6, 7], &[build_value(1); 3]);
+        let bytes = oof.to_bytes();
+
+        assert_eq!(
+            Oof::read_from_reader(&mut Trickle(&bytes[..60])),
+            Err(IoError::Proof(Oof::from_bytes(&bytes[..60]).unwrap_err()))
+        );
+        assert_eq!(
+            Oof::read_from_reader(&mut &bytes[..30]),
+            Err(IoError::Proof(Oof::from_bytes(&bytes[..30]).unwrap_err()))
+        );
+        assert_eq!(
+            Oof::read_from_reader(&mut &bytes[..2]),
+            Err(IoError::Proof(Oof::from_bytes(&bytes[..2]).unwrap_err()))
+        );
+    }
+
+    #[test]
+    fn huge_count() {
+        let bytes = [0xff, 0xff, 0xff, 0xff, 1, 2, 3];
+
+        assert_eq!(
+            Oof::read_from_reader(&mut &bytes[..]),
+            Err(IoError::Proof(Error::MalformedInput {
+                offset: 4,
+                expected: u32::MAX as usize * size_of::<K>(),
+                actual: 3,
+            }))
+        );
+        assert_eq!(
+            Oof::read_from_reader(&mut &bytes[..]),
+            Err(IoError::Proof(Oof::from_bytes(&bytes).unwrap_err()))
+        );
+    }
+
+    #[test]
+    fn short_write() {
+        let oof = Oof::new(&[2, 6, 7], &[build_value(1); 3]);
+        let mut writer = Bounded {
+            written: Vec::new(),
+            capacity: 10,
+        };
+
+        assert_eq!(oof.write_to_writer(&mut writer), Err(IoError::WriteZero));
+        assert_eq!(writer.written, oof.to_bytes()[..10]);
+    }
+}
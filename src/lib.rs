@@ -1,35 +1,114 @@
 #![no_std]
 
+pub mod accumulator;
+pub mod batch;
+pub mod cow;
+pub mod gindex;
 pub mod hash;
+pub mod interned;
+#[cfg(feature = "io")]
+pub mod io;
+pub mod values;
+pub mod verifier;
 
 extern crate alloc;
 
-use crate::hash::hash;
+use crate::gindex::{concat_gindex, is_in_subtree};
+use crate::hash::{hash, hash_bytes, keccak, ZERO_HASHES};
+use crate::values::u64_as_value;
 
-use alloc::collections::{BTreeMap, BTreeSet, BinaryHeap};
+pub use crate::accumulator::Accumulator;
+pub use crate::batch::BatchVerifier;
+pub use crate::cow::OofCow;
+pub use crate::hash::HashAlg;
+pub use crate::interned::Interned;
+pub use crate::verifier::{StepResult, Verifier};
+
+use alloc::borrow::Cow;
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
 use arrayref::array_ref;
-use bonsai::expand;
+use bonsai::{children, expand, general_index_to_subtree, sibling};
+use core::convert::TryFrom;
 use core::mem::size_of;
-use core::slice::{from_raw_parts, from_raw_parts_mut};
+use core::ops::Index;
+use core::slice::from_raw_parts;
 
-#[cfg(any(test, feature = "generate"))]
+use alloc::vec;
 use alloc::vec::Vec;
 
 type K = u128;
 type V = [u8; 32];
 type Map = BTreeMap<K, V>;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Oof {
     pub map: Map,
+    strict: bool,
+    domain: Option<V>,
+    padding: Option<u32>,
+    hasher: HashAlg,
 }
 
 #[derive(Debug, PartialEq)]
 pub enum Error {
     EntryNotFound(K),
+    MalformedInput {
+        offset: usize,
+        expected: usize,
+        actual: usize,
+    },
+    NoLeaves,
+    TooManyLeaves(usize),
+    EmptyProof,
+    TrailingData(usize),
+    MalformedBranch(K),
+    Conflict(K),
+    RedundantNode(K),
+    InvalidIndex(K),
+    InvalidValueLength {
+        key: K,
+        len: usize,
+    },
+    Unsorted(K),
+    DepthTooLarge(u32),
+    BitlistTooLong(usize),
+    DuplicateKey(K),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+impl Endianness {
+    #[cfg(any(test, feature = "generate"))]
+    fn key_to_bytes(self, key: K) -> [u8; size_of::<K>()] {
+        match self {
+            Endianness::Little => key.to_le_bytes(),
+            Endianness::Big => key.to_be_bytes(),
+        }
+    }
+
+    fn key_from_bytes(self, bytes: [u8; size_of::<K>()]) -> K {
+        match self {
+            Endianness::Little => K::from_le_bytes(bytes),
+            Endianness::Big => K::from_be_bytes(bytes),
+        }
+    }
 }
 
 impl Oof {
+    pub const fn empty() -> Self {
+        Self {
+            map: Map::new(),
+            strict: false,
+            domain: None,
+            padding: None,
+            hasher: HashAlg::Sha256,
+        }
+    }
+
     pub fn new(keys: &[K], values: &[V]) -> Self {
         let mut map = Map::new();
 
@@ -37,22 +116,208 @@ impl Oof {
             map.insert(keys[i], values[i]);
         }
 
-        Self { map }
+        Self::from_map(map)
+    }
+
+    /// Like `new`, but combines nodes with `alg` instead of SHA-256.
+    pub fn with_hasher(keys: &[K], values: &[V], alg: HashAlg) -> Self {
+        Self {
+            hasher: alg,
+            ..Self::new(keys, values)
+        }
+    }
+
+    /// Builds a proof that stores each distinct value only once.
+    pub fn new_interned(keys: &[K], values: &[V]) -> Interned {
+        Interned::new(keys, values)
     }
 
+    /// # Safety
+    ///
+    /// `data` must point to a valid blob in the format produced by `to_bytes`.
     pub unsafe fn from_raw(data: *mut u8) -> Self {
         let count = u32::from_le_bytes(*array_ref![from_raw_parts(data, 4), 0, 4]) as usize;
         let keys = data.offset(4) as *mut K;
         let values = data.offset(4 + (count * size_of::<K>()) as isize) as *mut V;
 
-        Self::new(
-            from_raw_parts_mut(keys, count),
-            from_raw_parts_mut(values, count),
-        )
+        let mut map = Map::new();
+
+        // Keys directly follow the 4 byte count, so they are not guaranteed to
+        // be aligned.
+        for i in 0..count {
+            map.insert(keys.add(i).read_unaligned(), values.add(i).read());
+        }
+
+        Self::from_map(map)
+    }
+
+    /// Like `from_raw`, but checks the blob's declared length against
+    /// `capacity` before reading anything past the count.
+    ///
+    /// # Safety
+    ///
+    /// `data` must be valid for reads of `capacity` bytes.
+    pub unsafe fn try_from_raw(data: *const u8, capacity: usize) -> Result<Self, Error> {
+        let data = from_raw_parts(data, capacity);
+
+        let count = region(data, 0, 4)?;
+        let count = u32::from_le_bytes(*array_ref![count, 0, 4]) as usize;
+
+        let len = count
+            .checked_mul(size_of::<K>() + size_of::<V>())
+            .and_then(|len| len.checked_add(4));
+
+        match len {
+            Some(len) if len <= capacity => Self::from_bytes(&data[..len]),
+            // Reports which region falls short of `capacity`.
+            _ => Self::from_bytes(data),
+        }
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        Self::from_bytes_with(data, Endianness::Little)
+    }
+
+    /// Reads a proof framed by a 4-byte little-endian length of the `from_bytes`
+    /// payload that follows it. Returns the proof and the number of bytes the
+    /// frame took up, so any data after it can be parsed next.
+    pub fn from_framed(data: &[u8]) -> Result<(Self, usize), Error> {
+        let len = region(data, 0, 4)?;
+        let len = u32::from_le_bytes(*array_ref![len, 0, 4]) as usize;
+
+        let payload = region(data, 4, len)?;
+        Ok((Self::from_bytes(payload)?, 4 + len))
+    }
+
+    /// Like `from_bytes`, but rejects proofs containing an internal node
+    /// alongside both of its children.
+    pub fn from_bytes_minimal(data: &[u8]) -> Result<Self, Error> {
+        let oof = Self::from_bytes(data)?;
+
+        let redundant = oof
+            .complete_pairs()
+            .map(|(parent, _, _)| parent)
+            .find(|parent| oof.map.contains_key(parent));
+
+        match redundant {
+            Some(parent) => Err(Error::RedundantNode(parent)),
+            None => Ok(oof),
+        }
+    }
+
+    pub fn from_bytes_with(data: &[u8], endianness: Endianness) -> Result<Self, Error> {
+        let count = region(data, 0, 4)?;
+        let count = u32::from_le_bytes(*array_ref![count, 0, 4]) as usize;
+
+        // The count is untrusted. A length that overflows saturates, so it can
+        // never fit in `data` and is reported by `region` as malformed.
+        let keys_len = count.saturating_mul(size_of::<K>());
+        let keys = region(data, 4, keys_len)?;
+
+        let values_offset = 4 + keys_len;
+        let values = region(data, values_offset, count.saturating_mul(size_of::<V>()))?;
+
+        let end = values_offset + values.len();
+        if data.len() > end {
+            return Err(Error::TrailingData(data.len() - end));
+        }
+
+        let map = keys
+            .chunks(size_of::<K>())
+            .zip(values.chunks(size_of::<V>()))
+            .map(|(key, value)| {
+                (
+                    endianness.key_from_bytes(*array_ref![key, 0, 16]),
+                    *array_ref![value, 0, 32],
+                )
+            })
+            .collect();
+
+        Ok(Self::from_map(map))
+    }
+
+    /// Builds a proof from values given as byte slices, each of which must be
+    /// exactly 32 bytes long.
+    pub fn from_dynamic(pairs: &[(K, &[u8])]) -> Result<Self, Error> {
+        let mut map = Map::new();
+
+        for (key, value) in pairs {
+            if value.len() != size_of::<V>() {
+                return Err(Error::InvalidValueLength {
+                    key: *key,
+                    len: value.len(),
+                });
+            }
+
+            map.insert(*key, *array_ref![value, 0, 32]);
+        }
+
+        Ok(Self::from_map(map))
+    }
+
+    /// Places `leaves` at the left-most positions of a tree of `depth`. The
+    /// remaining positions are zero padded.
+    pub fn from_leaves(leaves: &[V], depth: u32) -> Result<Self, Error> {
+        let first = 1u128
+            .checked_shl(depth)
+            .ok_or(Error::DepthTooLarge(depth))?;
+
+        if depth < usize::BITS && leaves.len() > 1 << depth {
+            return Err(Error::TooManyLeaves(leaves.len()));
+        }
+
+        let map = leaves
+            .iter()
+            .enumerate()
+            .map(|(i, leaf)| (first + i as K, *leaf))
+            .collect();
+
+        let mut oof = Self::from_map(map);
+        oof.set_zero_padding(depth);
+        Ok(oof)
+    }
+
+    /// Returns the `2^depth` leaves of a tree of `depth` in offset order.
+    pub fn into_leaf_array(self, depth: u32) -> Result<Vec<V>, Error> {
+        let first = 1u128
+            .checked_shl(depth)
+            .ok_or(Error::DepthTooLarge(depth))?;
+
+        (first..=first + (first - 1))
+            .map(|key| self.get_required(&key).copied())
+            .collect()
+    }
+
+    /// Builds a proof from per-leaf branches, where each branch lists the leaf's
+    /// siblings from the bottom of the tree up to the root.
+    pub fn from_branches(entries: &[(K, V, Vec<V>)]) -> Result<Self, Error> {
+        let mut map = Map::new();
+
+        for (key, value, branch) in entries {
+            if *key == 0 || branch.len() != depth(*key) as usize {
+                return Err(Error::MalformedBranch(*key));
+            }
+
+            insert_unique(&mut map, *key, *value)?;
+
+            let mut index = *key;
+            for node in branch {
+                insert_unique(&mut map, sibling(index), *node)?;
+                index /= 2;
+            }
+        }
+
+        Ok(Self::from_map(map))
     }
 
     pub fn from_map(map: Map) -> Self {
-        Self { map }
+        Self {
+            map,
+            strict: false,
+            domain: None,
+            padding: None,
+            hasher: HashAlg::Sha256,
+        }
     }
 
     #[cfg(any(test, feature = "generate"))]
@@ -62,10 +327,15 @@ impl Oof {
 
     #[cfg(any(test, feature = "generate"))]
     pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_bytes_with(Endianness::Little)
+    }
+
+    #[cfg(any(test, feature = "generate"))]
+    pub fn to_bytes_with(&self, endianness: Endianness) -> Vec<u8> {
         let keys: Vec<u8> = self
             .map
             .keys()
-            .flat_map(|k| k.to_le_bytes().to_vec())
+            .flat_map(|k| endianness.key_to_bytes(*k).to_vec())
             .collect();
 
         let values: Vec<u8> = self.map.values().flatten().cloned().collect();
@@ -76,94 +346,2866 @@ impl Oof {
         ret
     }
 
+    /// Returns every entry as an owned pair, in ascending key order.
+    pub fn to_entries(&self) -> Vec<(K, V)> {
+        self.map.iter().map(|(key, value)| (*key, *value)).collect()
+    }
+
+    /// Builds a proof from entries in strictly ascending key order, failing on
+    /// the first key that is out of order or repeated.
+    pub fn from_entries(entries: Vec<(K, V)>) -> Result<Self, Error> {
+        for pair in entries.windows(2) {
+            let (prev, key) = (pair[0].0, pair[1].0);
+
+            if prev == key {
+                return Err(Error::DuplicateKey(key));
+            }
+
+            if prev > key {
+                return Err(Error::Unsorted(key));
+            }
+        }
+
+        Ok(Self::from_map(entries.into_iter().collect()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Estimates the heap memory held by the proof's map. `BTreeMap` doesn't
+    /// expose its layout, so this assumes nodes of 11 entries, each with a
+    /// parent pointer, a length and 12 child pointers; the real figure depends
+    /// on how full the nodes are.
+    pub fn heap_size(&self) -> usize {
+        const NODE_CAPACITY: usize = 11;
+        const NODE_OVERHEAD: usize = (NODE_CAPACITY + 3) * size_of::<usize>();
+
+        let nodes = self.len().div_ceil(NODE_CAPACITY);
+        self.len() * (size_of::<K>() + size_of::<V>()) + nodes * NODE_OVERHEAD
+    }
+
+    /// Moves every entry out of the proof, leaving it empty.
+    pub fn drain(&mut self) -> impl Iterator<Item = (K, V)> {
+        core::mem::take(&mut self.map).into_iter()
+    }
+
+    /// Serializes the proof without any node that can be derived from the rest.
+    /// Reading it back with `from_compact_bytes` needs a `root` to fill the
+    /// internal nodes back in.
+    #[cfg(any(test, feature = "generate"))]
+    pub fn to_compact_bytes(&self) -> Vec<u8> {
+        let mut pruned = self.clone();
+        pruned.prune();
+        pruned.to_bytes()
+    }
+
+    pub fn from_compact_bytes(data: &[u8]) -> Result<Self, Error> {
+        Self::from_bytes(data)
+    }
+
+    /// Under zero padding, removes every node whose value is the zero hash for
+    /// its height, since refreshing regenerates it. If that would leave the
+    /// proof empty, its shallowest node is kept.
+    pub fn compress_zero_runs(&mut self) {
+        let padding = match self.padding {
+            Some(padding) => padding,
+            None => return,
+        };
+
+        let zeros = zero_table(self.hasher, padding);
+        let is_zero = |key: &K, value: &V| {
+            let height = padding.checked_sub(depth(*key));
+            height.is_some_and(|height| zeros[height as usize] == *value)
+        };
+
+        let first = self.map.keys().next().copied();
+        self.map
+            .retain(|key, value| *key == 0 || !is_zero(key, value));
+
+        if self.map.is_empty() {
+            if let Some(key) = first {
+                self.map.insert(key, zeros[(padding - depth(key)) as usize]);
+            }
+        }
+    }
+
+    /// Removes every node that can be derived from its present descendants.
+    pub fn prune(&mut self) {
+        let mut available = self.keys();
+        let mut cursor = available.iter().next_back().copied();
+
+        // Visiting in descending order means both children of a node are known
+        // to be available or not by the time the left one is reached.
+        while let Some(key) = cursor {
+            if key > 1 && key % 2 == 0 && available.contains(&(key + 1)) {
+                available.insert(key / 2);
+                self.map.remove(&(key / 2));
+            }
+
+            cursor = available.range(..key).next_back().copied();
+        }
+    }
+
     pub fn get(&self, key: &K) -> Option<&V> {
         self.map.get(key)
     }
 
+    pub fn get_required(&self, key: &K) -> Result<&V, Error> {
+        self.get(key).ok_or(Error::EntryNotFound(*key))
+    }
+
+    /// Returns the value of `key`'s sibling, if `key` has one and it is present.
+    pub fn sibling_value(&self, key: K) -> Option<&V> {
+        match key {
+            0 | 1 => None,
+            key => self.get(&sibling(key)),
+        }
+    }
+
     pub fn set(&mut self, key: K, value: V) -> Option<V> {
         let (_, _, parent) = expand(key);
         self.map.remove(&parent);
         self.map.insert(key, value)
     }
 
-    pub fn root(&mut self) -> Result<&V, Error> {
-        self.refresh()?;
-        Ok(self.get(&1).ok_or(Error::EntryNotFound(1))?)
+    /// Returns the parent of the left child `left_index` along with the hash of
+    /// the subtree roots `left` and `right` beneath it.
+    pub fn combine_roots(left_index: K, left: V, right: V) -> Result<(K, V), Error> {
+        if left_index < 2 || left_index % 2 == 1 {
+            return Err(Error::InvalidIndex(left_index));
+        }
+
+        Ok((left_index / 2, hash(&left, &right)))
     }
 
-    pub fn keys(&self) -> BTreeSet<K> {
-        self.map.keys().cloned().collect()
+    /// Returns whether `a` and `b` are the two children of the same parent.
+    pub fn are_siblings(&self, a: K, b: K) -> bool {
+        a > 1 && sibling(a) == b
+    }
+
+    /// Hashes the sibling pair `a` and `b` into their parent, which is added to
+    /// the proof if absent and must match if present.
+    pub fn verify_sibling_pair(&mut self, a: K, b: K) -> Result<V, Error> {
+        if !self.are_siblings(a, b) {
+            return Err(Error::InvalidIndex(b));
+        }
+
+        let (left, right) = (a.min(b), a.max(b));
+        let h = self
+            .hasher
+            .hash(self.get_required(&left)?, self.get_required(&right)?);
+
+        match self.get(&(left / 2)) {
+            Some(old) if *old != h => Err(Error::Conflict(left / 2)),
+            Some(_) => Ok(h),
+            None => {
+                self.set(left / 2, h);
+                Ok(h)
+            }
+        }
     }
 
-    fn refresh(&mut self) -> Result<(), Error> {
-        let mut keys: BinaryHeap<u128> = self.keys().into_iter().collect();
+    /// Sets `key` and returns the new root, rehashing only the path from `key`
+    /// up when every sibling along it is present. Otherwise falls back to a
+    /// full `root`. On error the proof is left as it was.
+    pub fn set_and_root(&mut self, key: K, value: V) -> Result<V, Error> {
+        if key == 0 {
+            return Err(Error::InvalidIndex(key));
+        }
 
-        while let Some(key) = keys.pop() {
-            if key <= 1 {
-                break;
+        let old = self.map.insert(key, value);
+
+        let mut evicted = Vec::new();
+        let mut ancestor = key / 2;
+        while ancestor >= 1 {
+            if let Some(value) = self.map.remove(&ancestor) {
+                evicted.push((ancestor, value));
             }
+            ancestor /= 2;
+        }
 
-            let (left, right, parent) = expand(key);
+        let result = self.path_root(key, value);
 
-            match (self.get(&left), self.get(&right), self.get(&parent)) {
-                (Some(l), Some(r), None) => {
-                    let h = hash(l, r);
-                    self.set(parent, h);
-                    keys.push(parent);
+        if result.is_err() {
+            let mut ancestor = key / 2;
+            while ancestor >= 1 {
+                self.map.remove(&ancestor);
+                ancestor /= 2;
+            }
+            self.map.extend(evicted);
+
+            match old {
+                Some(old) => self.map.insert(key, old),
+                None => self.map.remove(&key),
+            };
+        }
+
+        result
+    }
+
+    /// Hashes `value` at `key` up to the root for `set_and_root`, where every
+    /// ancestor of `key` has already been removed.
+    fn path_root(&mut self, key: K, value: V) -> Result<V, Error> {
+        self.check_derivable()?;
+
+        let (alg, padding) = (self.hasher, self.padding);
+        let zeros = zero_table(alg, padding.unwrap_or(0));
+        let padding = |key| {
+            let height = padding?.checked_sub(depth(key))?;
+            zeros.get(height as usize).copied()
+        };
+
+        let (mut key, mut node) = (key, value);
+        while key > 1 {
+            let other = sibling(key);
+            let other = match self.get(&other).copied().or_else(|| padding(other)) {
+                Some(other) => other,
+                None => {
+                    let mut full = self.clone();
+                    let root = full.root()?;
+                    *self = full;
+                    return Ok(root);
                 }
-                (Some(_), Some(_), Some(_)) => (),
-                (None, _, _) => return Err(Error::EntryNotFound(left)),
-                (_, None, _) => return Err(Error::EntryNotFound(right)),
             };
+
+            node = match key % 2 {
+                0 => alg.hash(&node, &other),
+                _ => alg.hash(&other, &node),
+            };
+            key /= 2;
+            self.map.insert(key, node);
+        }
+
+        match &self.domain {
+            Some(domain) => Ok(self.hasher.hash(&node, domain)),
+            None => Ok(node),
+        }
+    }
+
+    /// Applies `f` to every entry and drops all internal nodes with a child
+    /// present, so the next `root` recomputes them from the transformed values.
+    ///
+    /// Internal nodes without any children present are transformed like leaves
+    /// and kept; whether that is meaningful is up to the caller.
+    pub fn map_values(&mut self, mut f: impl FnMut(K, &mut V)) {
+        for (key, value) in self.map.iter_mut() {
+            f(*key, value);
+        }
+
+        let parents: Vec<K> = self.map.keys().filter(|&&k| k > 1).map(|k| k / 2).collect();
+
+        for parent in parents {
+            self.map.remove(&parent);
+        }
+    }
+
+    /// Moves the proof into a larger tree, so that its local root `1` ends up
+    /// at `at`. Zero padding and domain are dropped, as they only make sense
+    /// relative to the original root.
+    pub fn rebase(&self, at: K) -> Result<Oof, Error> {
+        if at == 0 {
+            return Err(Error::InvalidIndex(at));
+        }
+
+        let mut map = Map::new();
+
+        for (key, value) in self.map.iter() {
+            let key = concat_gindex(at, *key).ok_or(Error::InvalidIndex(*key))?;
+            map.insert(key, *value);
+        }
+
+        Ok(Oof {
+            map,
+            hasher: self.hasher,
+            ..Oof::empty()
+        })
+    }
+
+    /// Inserts every node of `subtree` with its local root `1` rebased onto `at`.
+    pub fn graft(&mut self, at: K, subtree: &Oof) -> Result<(), Error> {
+        let rebased = subtree.rebase(at)?;
+
+        for (key, value) in rebased.map.iter() {
+            match self.get(key) {
+                Some(old) if old != value => return Err(Error::Conflict(*key)),
+                _ => (),
+            }
+        }
+
+        // Insert parents after their children so `set` doesn't evict them.
+        for (key, value) in rebased.map.into_iter().rev() {
+            self.set(key, value);
         }
 
         Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use core::mem::transmute;
+    /// Drops every node in `at`'s subtree along with `at`'s ancestors, then
+    /// grafts `subtree` in its place.
+    pub fn replace_subtree(&mut self, at: K, subtree: &Oof) -> Result<(), Error> {
+        if at == 0 {
+            return Err(Error::InvalidIndex(at));
+        }
 
-    fn build_value(n: u8) -> [u8; 32] {
-        let mut tmp = [0u8; 32];
-        tmp[0] = n;
-        tmp
+        let mut replaced = self.clone();
+        replaced.map.retain(|key, _| !is_in_subtree(at, *key));
+
+        let mut ancestor = at / 2;
+        while ancestor >= 1 {
+            replaced.map.remove(&ancestor);
+            ancestor /= 2;
+        }
+
+        replaced.graft(at, subtree)?;
+        *self = replaced;
+
+        Ok(())
     }
 
-    #[test]
-    fn root() {
-        let mut keys = [2, 6, 7];
-        let mut values = [build_value(2), build_value(6), build_value(7)];
-        let mut oof = Oof::new(&mut keys, &mut values);
+    /// Splits the proof into the nodes at or above `depth` and one proof for
+    /// each subtree rooted at `depth` with nodes below it. Subtree roots that
+    /// are absent but derivable are added to the top as leaves.
+    pub fn split_at_depth(&self, depth: u32) -> (Oof, Vec<(K, Oof)>) {
+        let mut top = Map::new();
+        let mut bottoms: BTreeMap<K, Map> = BTreeMap::new();
 
-        let three = hash(&values[1], &values[2]);
-        let one = hash(&values[0], &three);
+        for (key, value) in self.map.iter() {
+            let d = gindex::depth(*key).unwrap_or(0);
+
+            if d <= depth {
+                top.insert(*key, *value);
+            } else {
+                let root = key >> (d - depth);
+                bottoms
+                    .entry(root)
+                    .or_default()
+                    .insert(general_index_to_subtree(root, *key), *value);
+            }
+        }
+
+        let bottoms: Vec<(K, Oof)> = bottoms
+            .into_iter()
+            .map(|(root, map)| {
+                let mut oof = Oof::from_map(map);
+                oof.padding = self.padding.and_then(|p| p.checked_sub(depth));
+                oof.hasher = self.hasher;
+                (root, oof)
+            })
+            .collect();
 
-        assert_eq!(oof.root(), Ok(&one));
+        for (root, oof) in bottoms.iter() {
+            if !top.contains_key(root) {
+                if let Ok(value) = oof.compute_root() {
+                    top.insert(*root, value);
+                }
+            }
+        }
+
+        let top = Oof {
+            map: top,
+            ..self.clone()
+        };
+
+        (top, bottoms)
     }
 
-    #[test]
-    fn from_blob() {
-        let count: u32 = 3;
+    /// When set, `root` and `verify` reject proofs without any structural
+    /// leaves, since such proofs can only be checked against supplied internals.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
 
-        let keys: [K; 3] = [1, 2, 3];
-        let values: [V; 3] = [build_value(1), build_value(2), build_value(3)];
+    /// Returns whether any node other than the root has no children present.
+    pub fn has_leaves(&self) -> bool {
+        self.map.keys().any(|&key| key > 1 && self.is_leaf(key))
+    }
 
-        let keys: [u8; 48] = unsafe { transmute(keys) };
-        let values: [u8; 96] = unsafe { transmute(values) };
+    /// Returns whether `key` has neither child present.
+    fn is_leaf(&self, key: K) -> bool {
+        let (left, right) = children(key);
+        !self.map.contains_key(&left) && !self.map.contains_key(&right)
+    }
 
-        let mut blob = [0u8; (4 + 48 + 96)];
-        blob[0..4].copy_from_slice(&count.to_le_bytes());
-        blob[4..52].copy_from_slice(&keys[..]);
-        blob[52..148].copy_from_slice(&values[..]);
+    /// Returns the SHA-256 digest of every structural leaf's little-endian key
+    /// followed by its value, in key order. Unlike `root`, it ignores internal
+    /// nodes and needs no structure beyond the leaves themselves. A root with
+    /// no children present counts as a leaf.
+    pub fn leaves_digest(&self) -> V {
+        let mut data = Vec::new();
 
-        let oof = unsafe { Oof::from_raw(blob[..].as_ptr() as *mut u8) };
+        for (key, value) in self.map.iter().filter(|(key, _)| self.is_leaf(**key)) {
+            data.extend_from_slice(&key.to_le_bytes());
+            data.extend_from_slice(value);
+        }
 
-        assert_eq!(oof.get(&1), Some(&build_value(1)));
-        assert_eq!(oof.get(&2), Some(&build_value(2)));
-        assert_eq!(oof.get(&3), Some(&build_value(3)));
-        assert_eq!(oof.get(&4), None);
+        hash_bytes(&data)
+    }
+
+    /// Mixes `tag` into the root, so identical trees committed under different
+    /// domains produce different roots.
+    pub fn set_domain(&mut self, tag: V) {
+        self.domain = Some(tag);
+    }
+
+    /// Returns the node at index `1` without deriving anything. Unlike `root`,
+    /// no domain is mixed in.
+    pub fn cached_root(&self) -> Option<&V> {
+        self.get(&1)
+    }
+
+    pub fn root(&mut self) -> Result<V, Error> {
+        let (alg, zeros) = (
+            self.hasher,
+            zero_table(self.hasher, self.padding.unwrap_or(0)),
+        );
+        self.root_inner(|l, r| alg.hash(l, r), &zeros, |_| None)
+    }
+
+    /// Like `root`, but also returns every node it had to add, both the
+    /// internal nodes it computed and any zero padding it filled in.
+    pub fn root_verbose(&mut self) -> Result<(V, BTreeMap<K, V>), Error> {
+        self.check_derivable()?;
+
+        let present = self.keys();
+        let mut derived = BTreeSet::new();
+        self.refresh_with_progress(|key| {
+            derived.insert(key);
+        })?;
+
+        let nodes = self
+            .map
+            .iter()
+            .filter(|(key, _)| derived.contains(key) || !present.contains(key))
+            .map(|(key, value)| (*key, *value))
+            .collect();
+
+        Ok((self.root()?, nodes))
+    }
+
+    /// Like `root`, but also reports whether the proof was minimal beforehand,
+    /// i.e. held no node whose children were both present too.
+    pub fn root_and_minimality(&mut self) -> Result<(V, bool), Error> {
+        let minimal = !self
+            .complete_pairs()
+            .any(|(parent, _, _)| self.map.contains_key(&parent));
+
+        Ok((self.root()?, minimal))
+    }
+
+    /// Treats any node of a tree of `depth` that is needed but absent as the
+    /// root of an empty subtree, i.e. the zero hash for its height.
+    pub fn set_zero_padding(&mut self, depth: u32) {
+        self.padding = Some(depth);
+    }
+
+    /// Derives the root using `combine` in place of the built-in `hash`.
+    /// Internal nodes that are already present are used as-is.
+    pub fn root_with(&mut self, combine: impl Fn(&V, &V) -> V) -> Result<V, Error> {
+        let zeros = zero_hashes(&combine, self.padding.unwrap_or(0));
+        self.root_inner(combine, &zeros, |_| None)
+    }
+
+    /// Derives the root, asking `oracle` for any node that is needed but absent
+    /// before giving up. Nodes supplied by the oracle are kept in the proof.
+    pub fn root_with_oracle(&mut self, oracle: impl FnMut(K) -> Option<V>) -> Result<V, Error> {
+        let (alg, zeros) = (
+            self.hasher,
+            zero_table(self.hasher, self.padding.unwrap_or(0)),
+        );
+        self.root_inner(|l, r| alg.hash(l, r), &zeros, oracle)
+    }
+
+    /// Derives the root using a precomputed table of zero hashes, which must
+    /// cover the padding depth.
+    pub(crate) fn root_with_zero_hashes(&mut self, zeros: &[V]) -> Result<V, Error> {
+        let alg = self.hasher;
+        self.root_inner(|l, r| alg.hash(l, r), zeros, |_| None)
+    }
+
+    fn root_inner(
+        &mut self,
+        combine: impl Fn(&V, &V) -> V,
+        zeros: &[V],
+        oracle: impl FnMut(K) -> Option<V>,
+    ) -> Result<V, Error> {
+        self.check_derivable()?;
+        self.derive(&combine, zeros, oracle, |_| (), Err)?;
+
+        let root = self.get_required(&1)?;
+
+        match &self.domain {
+            Some(domain) => Ok(combine(root, domain)),
+            None => Ok(*root),
+        }
+    }
+
+    pub fn verify(&mut self, root: &V) -> Result<bool, Error> {
+        Ok(self.root()? == *root)
+    }
+
+    /// Checks that every `(index, value)` in `entries` is part of the proof and
+    /// that the proof derives `root`.
+    pub fn verify_multi(&self, entries: &[(K, V)], root: &V) -> Result<bool, Error> {
+        for (key, value) in entries {
+            if self.get_required(key)? != value {
+                return Ok(false);
+            }
+        }
+
+        Ok(self.compute_root()? == *root)
+    }
+
+    /// Inserts the root of each child proof at its key and verifies the
+    /// combined proof against `root`.
+    pub fn verify_layered(&mut self, children: &[(K, Oof)], root: &V) -> Result<bool, Error> {
+        let roots = children
+            .iter()
+            .map(|(key, child)| Ok((*key, child.compute_root()?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        for (key, child_root) in roots {
+            self.set(key, child_root);
+        }
+
+        self.verify(root)
+    }
+
+    /// Inserts the root of `child` at `at` and drops every ancestor of `at`, so
+    /// the next `root` folds the child in.
+    pub fn set_subtree_root(&mut self, at: K, child: &mut Oof) -> Result<(), Error> {
+        if at == 0 {
+            return Err(Error::InvalidIndex(at));
+        }
+
+        let root = child.root()?;
+        self.map.insert(at, root);
+
+        let mut key = at / 2;
+        while key >= 1 {
+            self.map.remove(&key);
+            key /= 2;
+        }
+
+        Ok(())
+    }
+
+    pub fn compute_root(&self) -> Result<V, Error> {
+        self.clone().root()
+    }
+
+    /// Like `compute_root`, but keeps derived nodes in `scratch` instead of a
+    /// copy of the whole proof. `scratch` is cleared first.
+    ///
+    /// This saves cloning the proof, not every allocation: a `BTreeMap` frees
+    /// its nodes on `clear`, so each derived node is allocated again, and the
+    /// derivation still builds its list of keys and queue of derived parents.
+    pub fn compute_root_into(&self, scratch: &mut Map) -> Result<V, Error> {
+        self.check_derivable()?;
+        scratch.clear();
+
+        let (alg, zeros) = (
+            self.hasher,
+            zero_table(self.hasher, self.padding.unwrap_or(0)),
+        );
+        let mut overlay = Overlay {
+            base: &self.map,
+            scratch,
+            stale: BTreeSet::new(),
+        };
+        derive(
+            &mut overlay,
+            self.padding,
+            |l, r| alg.hash(l, r),
+            &zeros,
+            |_| None,
+            |_| (),
+            Err,
+        )?;
+
+        let root = *overlay.get(&1).ok_or(Error::EntryNotFound(1))?;
+
+        match &self.domain {
+            Some(domain) => Ok(self.hasher.hash(&root, domain)),
+            None => Ok(root),
+        }
+    }
+
+    pub fn equivalent(&self, other: &Oof) -> Result<bool, Error> {
+        Ok(self.compute_root()? == other.compute_root()?)
+    }
+
+    /// Returns whether both proofs commit to the same root, without handing
+    /// either root to the caller.
+    pub fn same_root_as(&self, other: &Oof) -> Result<bool, Error> {
+        self.equivalent(other)
+    }
+
+    /// Returns whether every node of `self` is also in `other` with the same
+    /// value.
+    pub fn is_subproof_of(&self, other: &Oof) -> bool {
+        let mut theirs = other.map.iter().peekable();
+
+        for (key, value) in self.map.iter() {
+            while theirs.next_if(|(k, _)| *k < key).is_some() {}
+
+            match theirs.next() {
+                Some((k, v)) if k == key && v == value => (),
+                _ => return false,
+            }
+        }
+
+        true
+    }
+
+    /// Returns the minimal proof of `field_gindex` against the overall root: the
+    /// node itself plus the sibling of every node on its path to the root.
+    pub fn field_proof(&self, field_gindex: K) -> Result<Oof, Error> {
+        self.prove(&[field_gindex])
+    }
+
+    /// Returns the minimal multiproof of `leaves`: the leaves themselves plus
+    /// every sibling along their paths that can't be derived from the others.
+    pub fn prove(&self, leaves: &[K]) -> Result<Oof, Error> {
+        let mut full = self.clone();
+        full.refresh()?;
+
+        let mut paths = BTreeSet::new();
+        for leaf in leaves {
+            let mut key = *leaf;
+            while key >= 1 && paths.insert(key) {
+                key /= 2;
+            }
+        }
+
+        let mut map = Map::new();
+        for leaf in leaves {
+            map.insert(*leaf, *full.get_required(leaf)?);
+        }
+
+        for key in paths.iter().filter(|&&key| key > 1) {
+            let sibling = sibling(*key);
+            if !paths.contains(&sibling) {
+                map.insert(sibling, *full.get_required(&sibling)?);
+            }
+        }
+
+        Ok(Oof { map, ..full })
+    }
+
+    /// Returns every absent node that can't be derived from the nodes present,
+    /// which is the least that has to be added before `root` can succeed.
+    pub fn missing_nodes(&self) -> BTreeSet<K> {
+        let padded = |key| self.padding.is_some_and(|padding| depth(key) <= padding);
+
+        let mut pending = self.keys();
+        let mut available = pending.clone();
+        let mut missing = BTreeSet::new();
+
+        while let Some(key) = pending.pop_last() {
+            if key <= 1 {
+                break;
+            }
+
+            let sibling = sibling(key);
+            if !available.contains(&sibling) && !padded(sibling) {
+                missing.insert(sibling);
+                available.insert(sibling);
+            }
+
+            if available.insert(key / 2) {
+                pending.insert(key / 2);
+            }
+        }
+
+        if !available.contains(&1) {
+            missing.insert(1);
+        }
+
+        missing
+    }
+
+    /// Returns how many hashes `root` would compute, without computing any.
+    /// Present nodes above a derived one count again, as they're recomputed,
+    /// and mixing in a domain counts as one more.
+    pub fn verification_cost(&self) -> usize {
+        self.derivation_order().len() + self.domain.is_some() as usize
+    }
+
+    /// Returns the keys of the nodes `refresh` would compute, in the order it
+    /// would compute them, up to the first missing node if there is one.
+    pub fn derivation_order(&self) -> Vec<K> {
+        let padded = |key| self.padding.is_some_and(|padding| depth(key) <= padding);
+
+        let mut pending = self.keys();
+        let mut present = pending.clone();
+        let mut order = Vec::new();
+
+        while let Some(key) = pending.pop_last() {
+            if key <= 1 {
+                break;
+            }
+
+            let (left, right, parent) = expand(key);
+            let available = |k| present.contains(&k) || padded(k);
+
+            if !available(left) || !available(right) {
+                break;
+            }
+
+            if !present.contains(&parent) {
+                order.push(parent);
+                present.insert(parent);
+                present.remove(&(parent / 2));
+                pending.insert(parent);
+            }
+        }
+
+        order
+    }
+
+    /// Checks an untrusted proof before any root is computed, returning the
+    /// first failure of, in order:
+    ///
+    /// - index validity: no key is zero, which has no place in the tree
+    ///   (duplicate keys can't occur, as the map holds one value per key);
+    /// - `validate_connectivity`;
+    /// - `verify_internal_consistency`.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.map.contains_key(&0) {
+            return Err(Error::InvalidIndex(0));
+        }
+
+        self.validate_connectivity()?;
+        self.verify_internal_consistency()
+    }
+
+    /// Checks that every node connects to the root, i.e. that `missing_nodes`
+    /// is empty, and reports the node `refresh` would fail on first.
+    pub fn validate_connectivity(&self) -> Result<(), Error> {
+        if self.is_empty() {
+            return Err(Error::EmptyProof);
+        }
+
+        match self.missing_nodes().into_iter().next_back() {
+            Some(key) => Err(Error::EntryNotFound(key)),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks that every present node with both children present is their
+    /// hash, reporting the deepest node that isn't.
+    pub fn verify_internal_consistency(&self) -> Result<(), Error> {
+        let pairs: Vec<_> = self.complete_pairs().collect();
+
+        for (parent, left, right) in pairs.into_iter().rev() {
+            if let Some(value) = self.get(&parent) {
+                if *value != self.hasher.hash(&self.map[&left], &self.map[&right]) {
+                    return Err(Error::Conflict(parent));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Yields `(parent, left, right)` for every pair of siblings present in the
+    /// map, regardless of whether the parent itself is present.
+    pub fn complete_pairs(&self) -> impl Iterator<Item = (K, K, K)> + '_ {
+        self.map
+            .keys()
+            .filter(move |&&key| key > 1 && key % 2 == 0 && self.map.contains_key(&(key + 1)))
+            .map(|&left| (left / 2, left, left + 1))
+    }
+
+    /// Returns whether the proof holds every leaf of a perfect tree as deep as
+    /// its deepest key.
+    pub fn is_perfect(&self) -> bool {
+        let depth = match self
+            .map
+            .keys()
+            .next_back()
+            .and_then(|key| gindex::depth(*key))
+        {
+            Some(depth) => depth,
+            None => return false,
+        };
+
+        let leaves = self.map.range(1 << depth..).count();
+        1usize.checked_shl(depth) == Some(leaves)
+    }
+
+    /// Returns whether all `2^depth` descendants of `root` at `depth` levels
+    /// below it are present, so the subtree can be verified on its own.
+    pub fn contains_complete_subtree(&self, root: K, depth: u32) -> bool {
+        let first = match concat_gindex(root, 1u128.checked_shl(depth).unwrap_or(0)) {
+            Some(first) => first,
+            None => return false,
+        };
+
+        let last = first + ((1 << depth) - 1);
+        1usize.checked_shl(depth) == Some(self.map.range(first..=last).count())
+    }
+
+    /// Returns the `(offset, value)` of every node at the proof's deepest
+    /// level, where the offset is its position within that level. Shallower
+    /// nodes are left out, as are offsets that don't fit in a `u64`.
+    pub fn indexed_leaves(&self) -> Vec<(u64, V)> {
+        let depth = match self
+            .map
+            .keys()
+            .next_back()
+            .and_then(|key| gindex::depth(*key))
+        {
+            Some(depth) => depth,
+            None => return Vec::new(),
+        };
+
+        self.map
+            .range(1 << depth..)
+            .filter_map(|(key, value)| Some((u64::try_from(key - (1 << depth)).ok()?, *value)))
+            .collect()
+    }
+
+    /// Returns the distinct depths at which the proof has nodes. A key of zero
+    /// has no depth and is skipped.
+    pub fn depth_levels(&self) -> BTreeSet<u32> {
+        self.keys_iter().filter_map(gindex::depth).collect()
+    }
+
+    pub fn keys(&self) -> BTreeSet<K> {
+        self.keys_iter().collect()
+    }
+
+    /// Yields the keys in ascending order without collecting them.
+    pub fn keys_iter(&self) -> impl Iterator<Item = K> + '_ {
+        self.map.keys().cloned()
+    }
+
+    fn check_derivable(&self) -> Result<(), Error> {
+        if self.is_empty() {
+            return Err(Error::EmptyProof);
+        }
+
+        if self.strict && !self.has_leaves() {
+            return Err(Error::NoLeaves);
+        }
+
+        Ok(())
+    }
+
+    /// Derives every missing internal node without reading the root.
+    pub fn refresh(&mut self) -> Result<(), Error> {
+        self.refresh_with_progress(|_| ())
+    }
+
+    /// Derives only the nodes under `target` that are needed to compute it and
+    /// returns its value. Nodes outside that subtree are left untouched.
+    pub fn refresh_until(&mut self, target: K) -> Result<&V, Error> {
+        if target == 0 {
+            return Err(Error::InvalidIndex(target));
+        }
+
+        if !self.map.contains_key(&target) {
+            let map = self
+                .map
+                .iter()
+                .filter(|(key, _)| is_in_subtree(target, **key))
+                .map(|(key, value)| (general_index_to_subtree(target, *key), *value))
+                .collect();
+
+            let mut subtree = Oof::from_map(map);
+            subtree.padding = self.padding.and_then(|p| p.checked_sub(depth(target)));
+            subtree.hasher = self.hasher;
+
+            // With nothing present below it, a padded target is an empty subtree.
+            if let (true, Some(height)) = (subtree.is_empty(), subtree.padding) {
+                let zeros = zero_table(subtree.hasher, height);
+                subtree.map.insert(1, zeros[height as usize]);
+            }
+
+            let global = |key| concat_gindex(target, key).unwrap_or(key);
+            subtree.refresh().map_err(|e| match e {
+                Error::EntryNotFound(key) => Error::EntryNotFound(global(key)),
+                e => e,
+            })?;
+
+            for (key, value) in subtree.map {
+                match global(key) {
+                    key if key == target => self.set(key, value),
+                    key => self.map.insert(key, value),
+                };
+            }
+        }
+
+        self.get_required(&target)
+    }
+
+    /// Derives every missing internal node, calling `on_hash` with the key of
+    /// each node as it's computed.
+    pub fn refresh_with_progress(&mut self, on_hash: impl FnMut(K)) -> Result<(), Error> {
+        let (alg, zeros) = (
+            self.hasher,
+            zero_table(self.hasher, self.padding.unwrap_or(0)),
+        );
+        self.derive(|l, r| alg.hash(l, r), &zeros, |_| None, on_hash, Err)
+    }
+
+    /// Like `refresh`, but instead of stopping at the first missing node it
+    /// skips whatever that node blocks, derives everything else and returns
+    /// every missing node found.
+    pub fn refresh_collect_errors(&mut self) -> Result<(), Vec<Error>> {
+        let (alg, zeros) = (
+            self.hasher,
+            zero_table(self.hasher, self.padding.unwrap_or(0)),
+        );
+        let mut errors = Vec::new();
+
+        let on_missing = |e| {
+            if !errors.contains(&e) {
+                errors.push(e);
+            }
+            Ok(())
+        };
+
+        self.derive(|l, r| alg.hash(l, r), &zeros, |_| None, |_| (), on_missing)
+            .map_err(|e| vec![e])?;
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn derive(
+        &mut self,
+        combine: impl Fn(&V, &V) -> V,
+        zeros: &[V],
+        oracle: impl FnMut(K) -> Option<V>,
+        on_hash: impl FnMut(K),
+        on_missing: impl FnMut(Error) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        derive(
+            &mut self.map,
+            self.padding,
+            combine,
+            zeros,
+            oracle,
+            on_hash,
+            on_missing,
+        )
+    }
+}
+
+/// Storage that `derive` reads present nodes from and writes derived ones to.
+trait Nodes {
+    fn keys(&self) -> Vec<K>;
+    fn get(&self, key: &K) -> Option<&V>;
+    fn insert(&mut self, key: K, value: V);
+    fn remove(&mut self, key: &K);
+}
+
+impl Nodes for Map {
+    fn keys(&self) -> Vec<K> {
+        self.keys().cloned().collect()
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        BTreeMap::get(self, key)
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        BTreeMap::insert(self, key, value);
+    }
+
+    fn remove(&mut self, key: &K) {
+        BTreeMap::remove(self, key);
+    }
+}
+
+/// A read-only proof with derived nodes written to `scratch`. A present node
+/// removed because something below it was derived is hidden as stale.
+struct Overlay<'a> {
+    base: &'a Map,
+    scratch: &'a mut Map,
+    stale: BTreeSet<K>,
+}
+
+impl Nodes for Overlay<'_> {
+    fn keys(&self) -> Vec<K> {
+        self.base.keys().cloned().collect()
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        match self.scratch.get(key) {
+            Some(value) => Some(value),
+            None if self.stale.contains(key) => None,
+            None => self.base.get(key),
+        }
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        self.scratch.insert(key, value);
+    }
+
+    fn remove(&mut self, key: &K) {
+        self.scratch.remove(key);
+        self.stale.insert(*key);
+    }
+}
+
+/// Derives every missing internal node of `nodes` that its present nodes allow.
+fn derive(
+    nodes: &mut impl Nodes,
+    padding: Option<u32>,
+    combine: impl Fn(&V, &V) -> V,
+    zeros: &[V],
+    mut oracle: impl FnMut(K) -> Option<V>,
+    mut on_hash: impl FnMut(K),
+    mut on_missing: impl FnMut(Error) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let mut fetch = |key| {
+        oracle(key).or_else(|| {
+            let height = padding?.checked_sub(depth(key))?;
+            zeros.get(height as usize).copied()
+        })
+    };
+
+    // Keys are visited in descending order. A parent is always smaller than
+    // the child that derived it, so parents are derived in descending order
+    // too and can be merged in from a queue as the sorted keys are consumed.
+    let mut keys = nodes.keys();
+    let mut derived = VecDeque::new();
+    let mut blocked = BTreeSet::new();
+
+    loop {
+        let key = match (keys.last(), derived.front()) {
+            (Some(k), Some(d)) if d > k => derived.pop_front(),
+            (Some(_), _) => keys.pop(),
+            (None, _) => derived.pop_front(),
+        };
+
+        let key = match key {
+            Some(key) => key,
+            None => break,
+        };
+
+        if key <= 1 {
+            break;
+        }
+
+        let (left, right, parent) = expand(key);
+
+        for index in [left, right].iter() {
+            if nodes.get(index).is_none() {
+                if let Some(value) = fetch(*index) {
+                    nodes.insert(*index, value);
+                }
+            }
+        }
+
+        match (nodes.get(&left), nodes.get(&right), nodes.get(&parent)) {
+            (Some(l), Some(r), None) => {
+                let h = combine(l, r);
+                nodes.remove(&(parent / 2));
+                nodes.insert(parent, h);
+                derived.push_back(parent);
+                on_hash(parent);
+            }
+            (Some(_), Some(_), Some(_)) => (),
+            (l, _, _) => {
+                // A node that is only missing because something below it
+                // was is not reported again.
+                let missing = if l.is_none() { left } else { right };
+                if !blocked.contains(&missing) {
+                    on_missing(Error::EntryNotFound(missing))?;
+                }
+                blocked.insert(parent);
+            }
+        };
+    }
+
+    Ok(())
+}
+
+fn insert_unique(map: &mut Map, key: K, value: V) -> Result<(), Error> {
+    match map.insert(key, value) {
+        Some(old) if old != value => Err(Error::Conflict(key)),
+        _ => Ok(()),
+    }
+}
+
+/// Returns the depth of a key already known to be non-zero.
+fn depth(key: K) -> u32 {
+    gindex::depth(key).expect("generalized index 0 has no depth")
+}
+
+fn region(data: &[u8], offset: usize, len: usize) -> Result<&[u8], Error> {
+    offset
+        .checked_add(len)
+        .and_then(|end| data.get(offset..end))
+        .ok_or_else(|| Error::MalformedInput {
+            offset,
+            expected: len,
+            actual: data.len().saturating_sub(offset),
+        })
+}
+
+/// Returns the minimal depth of a tree with at least `n` leaves.
+pub fn depth_for_leaves(n: u64) -> u32 {
+    match n {
+        0 => 0,
+        n => u64::BITS - (n - 1).leading_zeros(),
+    }
+}
+
+/// Splits `data` into 32 byte chunks, zero-padding the last one, and returns the
+/// root of a tree of `depth` with the chunks as its left-most leaves.
+pub fn pack_and_merkleize(data: &[u8], depth: u32) -> Result<V, Error> {
+    let chunks = data
+        .chunks(size_of::<V>())
+        .map(|chunk| {
+            let mut leaf = [0u8; 32];
+            leaf[0..chunk.len()].copy_from_slice(chunk);
+            leaf
+        })
+        .collect();
+
+    merkleize(chunks, depth)
+}
+
+/// Returns the root of a tree of `depth` whose left-most `leaf_count` leaves
+/// are `f(0)`, `f(1)`, ... and the rest zero. Leaves are hashed in as they're
+/// produced, so at most one node per level is held at a time.
+pub fn root_from_fn(depth: u32, leaf_count: u64, mut f: impl FnMut(u64) -> V) -> Result<V, Error> {
+    if depth < u64::BITS && leaf_count > 1 << depth {
+        // A count beyond `usize` can only be reported as the largest one.
+        let count = usize::try_from(leaf_count).unwrap_or(usize::MAX);
+        return Err(Error::TooManyLeaves(count));
+    }
+
+    let zeros = zero_table(HashAlg::Sha256, depth);
+    let mut stack: Vec<(u32, V)> = Vec::with_capacity(depth as usize + 1);
+
+    let merge = |stack: &mut Vec<(u32, V)>| {
+        while stack.len() >= 2 && stack[stack.len() - 1].0 == stack[stack.len() - 2].0 {
+            let (height, right) = stack.pop().unwrap();
+            let (_, left) = stack.pop().unwrap();
+            stack.push((height + 1, hash(&left, &right)));
+        }
+    };
+
+    for offset in 0..leaf_count {
+        stack.push((0, f(offset)));
+        merge(&mut stack);
+    }
+
+    // Every node left on the stack is a left child, so it pairs with a zero
+    // subtree of its own height.
+    loop {
+        match stack.last() {
+            None => return Ok(zeros[depth as usize]),
+            Some((height, root)) if *height == depth && stack.len() == 1 => return Ok(*root),
+            Some(&(height, node)) => {
+                stack.pop();
+                stack.push((height + 1, hash(&node, &zeros[height as usize])));
+                merge(&mut stack);
+            }
+        }
+    }
+}
+
+/// Returns the SSZ root of a bitlist of `bits` with a limit of `max_len` bits.
+pub fn bitlist_root(bits: &[bool], max_len: u64) -> Result<V, Error> {
+    if bits.len() as u64 > max_len {
+        return Err(Error::BitlistTooLong(bits.len()));
+    }
+
+    let mut bytes = vec![0u8; bits.len().div_ceil(8)];
+    for (i, _) in bits.iter().enumerate().filter(|(_, bit)| **bit) {
+        bytes[i / 8] |= 1 << (i % 8);
+    }
+
+    let chunks = max_len.div_ceil(256);
+    let root = pack_and_merkleize(&bytes, depth_for_leaves(chunks))?;
+
+    Ok(mix_in_length(&root, bits.len() as u64))
+}
+
+fn mix_in_length(root: &V, len: u64) -> V {
+    hash(root, &u64_as_value(len))
+}
+
+/// Returns the zero table of `alg` up to `depth`, borrowing the static one
+/// when it applies and is deep enough.
+fn zero_table(alg: HashAlg, depth: u32) -> Cow<'static, [V]> {
+    match alg {
+        HashAlg::Sha256 => match ZERO_HASHES.get(..=depth as usize) {
+            Some(zeros) => Cow::Borrowed(zeros),
+            None => Cow::Owned(zero_hashes(hash, depth)),
+        },
+        HashAlg::Keccak256 => Cow::Owned(zero_hashes(keccak, depth)),
+    }
+}
+
+/// Returns the roots of empty subtrees of height `0..=depth`.
+pub(crate) fn zero_hashes(combine: impl Fn(&V, &V) -> V, depth: u32) -> Vec<V> {
+    let mut zeros = vec![[0u8; 32]];
+
+    for i in 0..depth as usize {
+        zeros.push(combine(&zeros[i], &zeros[i]));
+    }
+
+    zeros
+}
+
+fn merkleize(mut nodes: Vec<V>, depth: u32) -> Result<V, Error> {
+    if depth < usize::BITS && nodes.len() > 1 << depth {
+        return Err(Error::TooManyLeaves(nodes.len()));
+    }
+
+    let mut zero = [0u8; 32];
+
+    for _ in 0..depth {
+        if nodes.len() % 2 == 1 {
+            nodes.push(zero);
+        }
+
+        nodes = nodes
+            .chunks(2)
+            .map(|pair| hash(&pair[0], &pair[1]))
+            .collect();
+        zero = hash(&zero, &zero);
+    }
+
+    Ok(nodes.first().copied().unwrap_or(zero))
+}
+
+impl TryFrom<&[u8]> for Oof {
+    type Error = Error;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        Self::from_bytes(data)
+    }
+}
+
+impl Index<K> for Oof {
+    type Output = V;
+
+    fn index(&self, key: K) -> &V {
+        match self.get(&key) {
+            Some(value) => value,
+            None => panic!("no entry for generalized index {}", key),
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unnecessary_mut_passed)]
+mod tests {
+    use super::*;
+    use core::mem::transmute;
+
+    fn build_value(n: u8) -> [u8; 32] {
+        let mut tmp = [0u8; 32];
+        tmp[0] = n;
+        tmp
+    }
+
+    fn from_hex(s: &str) -> V {
+        let mut out = [0u8; 32];
+        for (i, byte) in out.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).unwrap();
+        }
+        out
+    }
+
+    #[test]
+    fn root() {
+        let mut keys = [2, 6, 7];
+        let mut values = [build_value(2), build_value(6), build_value(7)];
+        let mut oof = Oof::new(&mut keys, &mut values);
+
+        let three = hash(&values[1], &values[2]);
+        let one = hash(&values[0], &three);
+
+        assert_eq!(oof.root(), Ok(one));
+    }
+
+    fn heap_refresh(oof: &mut Oof) -> Result<(), Error> {
+        let mut keys: alloc::collections::BinaryHeap<K> = oof.keys_iter().collect();
+
+        while let Some(key) = keys.pop() {
+            if key <= 1 {
+                break;
+            }
+
+            let (left, right, parent) = expand(key);
+
+            match (oof.get(&left), oof.get(&right), oof.get(&parent)) {
+                (Some(l), Some(r), None) => {
+                    let h = hash(l, r);
+                    oof.set(parent, h);
+                    keys.push(parent);
+                }
+                (Some(_), Some(_), Some(_)) => (),
+                (None, _, _) => return Err(Error::EntryNotFound(left)),
+                (_, None, _) => return Err(Error::EntryNotFound(right)),
+            };
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn refresh_matches_heap() {
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+
+        for _ in 0..200 {
+            let depth = 1 + next() % 10;
+            let mut keys = BTreeSet::new();
+
+            for _ in 0..1 + next() % 8 {
+                let mut key = (1 << depth) + (next() % (1 << depth)) as K;
+                keys.insert(key);
+
+                while key > 1 {
+                    // Occasionally drop a sibling or add the parent to exercise the
+                    // error and pre-computed paths.
+                    match next() % 16 {
+                        0 => (),
+                        1 => {
+                            keys.insert(key / 2);
+                        }
+                        _ => {
+                            keys.insert(sibling(key));
+                        }
+                    }
+                    key /= 2;
+                }
+            }
+
+            let values: Vec<V> = keys.iter().map(|k| build_value(*k as u8)).collect();
+            let keys: Vec<K> = keys.into_iter().collect();
+
+            let mut expected = Oof::new(&keys, &values);
+            let mut actual = expected.clone();
+
+            assert_eq!(actual.refresh(), heap_refresh(&mut expected));
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn cached_root() {
+        let mut oof = Oof::new(&[1, 6], &[build_value(1), build_value(6)]);
+
+        assert_eq!(oof.cached_root(), Some(&build_value(1)));
+        assert_eq!(oof.root(), Err(Error::EntryNotFound(7)));
+
+        let mut oof = Oof::new(&[2, 3], &[build_value(2), build_value(3)]);
+        assert_eq!(oof.cached_root(), None);
+
+        let root = oof.root().unwrap();
+        assert_eq!(oof.cached_root(), Some(&root));
+    }
+
+    #[test]
+    fn refresh() {
+        let keys = [2, 6, 14, 15];
+        let values = [
+            build_value(2),
+            build_value(6),
+            build_value(14),
+            build_value(15),
+        ];
+        let mut oof = Oof::new(&keys, &values);
+
+        assert_eq!(oof.refresh(), Ok(()));
+        assert_eq!(
+            oof.keys(),
+            [1, 2, 3, 6, 7, 14, 15].iter().cloned().collect()
+        );
+        assert_eq!(oof.cached_root(), oof.compute_root().ok().as_ref());
+
+        let mut partial = Oof::new(&[2, 6], &[build_value(2), build_value(6)]);
+        assert_eq!(partial.refresh(), Err(Error::EntryNotFound(7)));
+    }
+
+    #[test]
+    fn refresh_with_progress() {
+        let keys = [2, 6, 14, 15];
+        let values = [
+            build_value(2),
+            build_value(6),
+            build_value(14),
+            build_value(15),
+        ];
+        let mut oof = Oof::new(&keys, &values);
+
+        let mut hashed = Vec::new();
+        oof.refresh_with_progress(|key| hashed.push(key)).unwrap();
+        assert_eq!(hashed, [7, 3, 1]);
+
+        hashed.clear();
+        oof.refresh_with_progress(|key| hashed.push(key)).unwrap();
+        assert!(hashed.is_empty());
+    }
+
+    #[test]
+    fn root_with_oracle() {
+        let leaves: Vec<V> = (8..16).map(build_value).collect();
+        let keys: Vec<K> = (8..16).collect();
+        let full = Oof::new(&keys, &leaves);
+
+        let mut oof = full.field_proof(10).unwrap();
+        let mut partial = Oof::new(&[10], &[leaves[2]]);
+
+        let mut queried = Vec::new();
+        let root = partial.root_with_oracle(|key| {
+            queried.push(key);
+            oof.map.remove(&key)
+        });
+
+        assert_eq!(root, full.compute_root());
+        assert_eq!(queried, [11, 4, 3]);
+        assert_eq!(
+            Oof::new(&[10], &[leaves[2]]).root_with_oracle(|_| None),
+            Err(Error::EntryNotFound(11))
+        );
+    }
+
+    #[test]
+    fn root_with() {
+        let keys = [2, 6, 7];
+        let values = [build_value(2), build_value(6), build_value(7)];
+        let oof = Oof::new(&keys, &values);
+
+        let expected = oof.clone().root().unwrap();
+        assert_eq!(oof.clone().root_with(hash), Ok(expected));
+
+        let xor = |l: &V, r: &V| {
+            let mut out = *l;
+            out.iter_mut().zip(r.iter()).for_each(|(o, r)| *o ^= r);
+            out
+        };
+
+        let mut custom = build_value(2);
+        custom[0] ^= 6 ^ 7;
+        assert_eq!(oof.clone().root_with(xor), Ok(custom));
+    }
+
+    #[test]
+    fn domain() {
+        let keys = [2, 3];
+        let values = [build_value(2), build_value(3)];
+        let plain = Oof::new(&keys, &values);
+
+        let mut first = plain.clone();
+        first.set_domain(build_value(1));
+
+        let mut second = plain.clone();
+        second.set_domain(build_value(2));
+
+        let root = plain.compute_root().unwrap();
+        assert_eq!(first.compute_root(), Ok(hash(&root, &build_value(1))));
+        assert_ne!(first.compute_root(), second.compute_root());
+        assert_ne!(first.compute_root(), plain.compute_root());
+    }
+
+    #[test]
+    fn zero_padding() {
+        let mut oof = Oof::new(&[4, 5], &[build_value(4), build_value(5)]);
+        let expected = hash(
+            &hash(&build_value(4), &build_value(5)),
+            &hash(&[0u8; 32], &[0u8; 32]),
+        );
+
+        assert_eq!(oof.clone().root(), Err(Error::EntryNotFound(3)));
+
+        oof.set_zero_padding(2);
+        assert_eq!(oof.root(), Ok(expected));
+        assert_eq!(oof.get(&3), Some(&hash(&[0u8; 32], &[0u8; 32])));
+    }
+
+    #[test]
+    fn map_values() {
+        let keys = [2, 6, 7];
+        let values = [build_value(2), build_value(6), build_value(7)];
+        let mut oof = Oof::new(&keys, &values);
+        oof.root().unwrap();
+
+        oof.map_values(|_, value| value[31] ^= 0xff);
+
+        let mut tagged = values;
+        for value in tagged.iter_mut() {
+            value[31] ^= 0xff;
+        }
+
+        let three = hash(&tagged[1], &tagged[2]);
+        let one = hash(&tagged[0], &three);
+
+        assert_eq!(oof.keys(), keys.iter().cloned().collect());
+        assert_eq!(oof.root(), Ok(one));
+    }
+
+    #[test]
+    fn empty() {
+        let mut oof = Oof::empty();
+
+        assert!(oof.is_empty());
+        assert_eq!(oof.len(), 0);
+        assert_eq!(oof, Oof::default());
+        assert_eq!(oof.root(), Err(Error::EmptyProof));
+    }
+
+    #[test]
+    fn from_branches() {
+        let leaves: Vec<V> = (4..8).map(build_value).collect();
+        let full = Oof::new(&[4, 5, 6, 7], &leaves);
+        let root = full.compute_root().unwrap();
+
+        let five = hash(&leaves[0], &leaves[1]);
+        let three = hash(&leaves[2], &leaves[3]);
+
+        let oof = Oof::from_branches(&[
+            (4, leaves[0], vec![leaves[1], three]),
+            (5, leaves[1], vec![leaves[0], three]),
+        ])
+        .unwrap();
+
+        assert_eq!(oof.keys(), [3, 4, 5].iter().cloned().collect());
+        assert_eq!(oof.compute_root(), Ok(root));
+
+        assert_eq!(
+            Oof::from_branches(&[
+                (4, leaves[0], vec![leaves[1], three]),
+                (6, leaves[2], vec![leaves[3], five]),
+                (7, leaves[3], vec![leaves[2], build_value(0)]),
+            ]),
+            Err(Error::Conflict(2))
+        );
+        assert_eq!(
+            Oof::from_branches(&[(4, leaves[0], vec![leaves[1]])]),
+            Err(Error::MalformedBranch(4))
+        );
+    }
+
+    #[test]
+    fn from_dynamic() {
+        let two = build_value(2).to_vec();
+        let three = build_value(3).to_vec();
+
+        assert_eq!(
+            Oof::from_dynamic(&[(2, &two), (3, &three)]),
+            Ok(Oof::new(&[2, 3], &[build_value(2), build_value(3)]))
+        );
+        assert_eq!(
+            Oof::from_dynamic(&[(2, &two), (3, &three[..31])]),
+            Err(Error::InvalidValueLength { key: 3, len: 31 })
+        );
+    }
+
+    #[test]
+    fn leaf_array() {
+        let leaves: Vec<V> = (0..8).map(build_value).collect();
+
+        let oof = Oof::from_leaves(&leaves, 3).unwrap();
+        assert_eq!(oof.into_leaf_array(3), Ok(leaves.clone()));
+
+        let oof = Oof::from_leaves(&leaves[..5], 3).unwrap();
+        assert_eq!(oof.into_leaf_array(3), Err(Error::EntryNotFound(13)));
+
+        assert_eq!(Oof::from_leaves(&leaves, 2), Err(Error::TooManyLeaves(8)));
+        assert_eq!(
+            Oof::from_leaves(&leaves, 128),
+            Err(Error::DepthTooLarge(128))
+        );
+        assert_eq!(
+            Oof::from_leaves(&leaves, 3).unwrap().into_leaf_array(130),
+            Err(Error::DepthTooLarge(130))
+        );
+    }
+
+    #[test]
+    fn drain() {
+        let keys = [2, 6, 7];
+        let values = [build_value(2), build_value(6), build_value(7)];
+        let mut oof = Oof::new(&keys, &values);
+
+        let drained: Vec<_> = oof.drain().collect();
+
+        assert!(oof.is_empty());
+        assert_eq!(
+            drained,
+            keys.iter()
+                .cloned()
+                .zip(values.iter().cloned())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn graft() {
+        let subtree = Oof::new(&[2, 3], &[build_value(10), build_value(11)]);
+        let mut oof = Oof::new(&[3, 4], &[build_value(3), build_value(4)]);
+
+        oof.graft(5, &subtree).unwrap();
+
+        let five = hash(&build_value(10), &build_value(11));
+        let two = hash(&build_value(4), &five);
+        assert_eq!(oof.keys(), [3, 4, 10, 11].iter().cloned().collect());
+        assert_eq!(oof.root(), Ok(hash(&two, &build_value(3))));
+
+        let conflicting = Oof::new(&[1], &[build_value(0)]);
+        assert_eq!(oof.graft(3, &conflicting), Err(Error::Conflict(3)));
+    }
+
+    #[test]
+    fn replace_subtree() {
+        let leaves: Vec<V> = (4..8).map(build_value).collect();
+        let mut oof = Oof::new(&[4, 5, 6, 7], &leaves);
+        oof.root().unwrap();
+
+        let subtree = Oof::new(
+            &[2, 6, 7],
+            &[build_value(1), build_value(2), build_value(3)],
+        );
+        oof.replace_subtree(3, &subtree).unwrap();
+
+        assert_eq!(oof.keys(), [2, 4, 5, 6, 14, 15].iter().cloned().collect());
+
+        let two = hash(&leaves[0], &leaves[1]);
+        let three = hash(&build_value(1), &hash(&build_value(2), &build_value(3)));
+        assert_eq!(oof.root(), Ok(hash(&two, &three)));
+    }
+
+    #[test]
+    fn split_at_depth() {
+        let keys = [2, 6, 14, 15, 28, 29];
+        let values: Vec<V> = keys.iter().map(|k| build_value(*k as u8)).collect();
+        let oof = Oof::new(&keys, &values);
+
+        let (top, bottoms) = oof.split_at_depth(2);
+
+        assert_eq!(top.keys(), [2, 6, 7].iter().cloned().collect());
+        assert_eq!(bottoms.len(), 1);
+        assert_eq!(bottoms[0].0, 7);
+        assert_eq!(bottoms[0].1.keys(), [2, 3, 4, 5].iter().cloned().collect());
+        assert_eq!(bottoms[0].1.get(&4), Some(&build_value(28)));
+
+        let mut joined = top.clone();
+        for (at, bottom) in bottoms.iter() {
+            joined.graft(*at, bottom).unwrap();
+        }
+
+        assert_eq!(top.compute_root(), oof.compute_root());
+        assert_eq!(joined.compute_root(), oof.compute_root());
+    }
+
+    #[test]
+    fn compute_root_into() {
+        let mut scratch = Map::new();
+        scratch.insert(99, build_value(99));
+
+        let keys = [2, 6, 14, 15];
+        let values = [
+            build_value(2),
+            build_value(6),
+            build_value(14),
+            build_value(15),
+        ];
+        let oof = Oof::new(&keys, &values);
+
+        assert_eq!(oof.compute_root_into(&mut scratch), oof.compute_root());
+        assert_eq!(scratch.keys().cloned().collect::<Vec<_>>(), [1, 3, 7]);
+        assert_eq!(oof.get(&1), None);
+
+        let mut padded = Oof::new(&[4], &[build_value(4)]);
+        padded.set_zero_padding(2);
+        assert_eq!(
+            padded.compute_root_into(&mut scratch),
+            padded.compute_root()
+        );
+
+        let partial = Oof::new(&[2, 6], &[build_value(2), build_value(6)]);
+        assert_eq!(
+            partial.compute_root_into(&mut scratch),
+            Err(Error::EntryNotFound(7))
+        );
+
+        let unused = Oof::new(
+            &[1, 3, 6],
+            &[build_value(1), build_value(3), build_value(6)],
+        );
+        assert_eq!(
+            unused.compute_root_into(&mut scratch),
+            unused.compute_root()
+        );
+
+        // 1 is stale once 3 is derived beneath it.
+        let stale = Oof::new(
+            &[1, 2, 6, 7],
+            &[
+                build_value(1),
+                build_value(2),
+                build_value(6),
+                build_value(7),
+            ],
+        );
+        assert_eq!(stale.compute_root_into(&mut scratch), stale.compute_root());
+        assert_ne!(stale.compute_root_into(&mut scratch), Ok(build_value(1)));
+    }
+
+    #[test]
+    fn equivalent() {
+        let values = [build_value(2), build_value(6), build_value(7)];
+        let three = hash(&values[1], &values[2]);
+
+        let leaves = Oof::new(&[2, 6, 7], &values);
+        let partial = Oof::new(&[2, 3], &[values[0], three]);
+        let other = Oof::new(&[2, 3], &[values[0], build_value(3)]);
+
+        assert_eq!(leaves.equivalent(&partial), Ok(true));
+        assert_eq!(partial.equivalent(&leaves), Ok(true));
+        assert_eq!(leaves.equivalent(&other), Ok(false));
+        assert_eq!(leaves.get(&1), None);
+    }
+
+    #[test]
+    fn field_proof() {
+        let keys = [4, 5, 6, 7];
+        let values = [
+            build_value(4),
+            build_value(5),
+            build_value(6),
+            build_value(7),
+        ];
+        let oof = Oof::new(&keys, &values);
+
+        let proof = oof.field_proof(6).unwrap();
+
+        assert_eq!(proof.keys(), [2, 6, 7].iter().cloned().collect());
+        assert_eq!(proof.get(&6), Some(&build_value(6)));
+        assert_eq!(proof.compute_root(), oof.compute_root());
+        assert_eq!(oof.field_proof(8), Err(Error::EntryNotFound(8)));
+    }
+
+    #[test]
+    fn is_perfect() {
+        let perfect = Oof::new(&[4, 5, 6, 7], &[build_value(0); 4]);
+        assert!(perfect.is_perfect());
+
+        let mut with_internals = perfect.clone();
+        with_internals.root().unwrap();
+        assert!(with_internals.is_perfect());
+
+        let missing = Oof::new(&[3, 4, 5], &[build_value(0); 3]);
+        assert!(!missing.is_perfect());
+
+        assert!(Oof::new(&[1], &[build_value(0)]).is_perfect());
+        assert!(!Oof::empty().is_perfect());
+    }
+
+    #[test]
+    fn depth_levels() {
+        let oof = Oof::new(&[4, 10, 11, 3], &[build_value(0); 4]);
+        assert_eq!(oof.depth_levels(), [1, 2, 3].iter().cloned().collect());
+
+        let oof = Oof::new(&[4, 5, 12, 13, 7], &[build_value(0); 5]);
+        assert_eq!(oof.depth_levels(), [2, 3].iter().cloned().collect());
+
+        let oof = Oof::new(&[0, 1 << 100], &[build_value(0); 2]);
+        assert_eq!(oof.depth_levels(), [100].iter().cloned().collect());
+        assert!(!Oof::new(&[0], &[build_value(0)]).is_perfect());
+    }
+
+    #[test]
+    fn keys_iter() {
+        let oof = Oof::new(&[7, 2, 6], &[build_value(0); 3]);
+
+        assert_eq!(oof.keys_iter().collect::<Vec<_>>(), [2, 6, 7]);
+        assert_eq!(oof.keys_iter().collect::<BTreeSet<_>>(), oof.keys());
+    }
+
+    #[test]
+    fn get_required() {
+        let oof = Oof::new(&[2, 3], &[build_value(2), build_value(3)]);
+
+        assert_eq!(oof.get_required(&2), Ok(&build_value(2)));
+        assert_eq!(oof.get_required(&4), Err(Error::EntryNotFound(4)));
+    }
+
+    #[test]
+    fn sibling_value() {
+        let oof = Oof::new(
+            &[1, 2, 3, 6],
+            &[
+                build_value(1),
+                build_value(2),
+                build_value(3),
+                build_value(6),
+            ],
+        );
+
+        assert_eq!(oof.sibling_value(2), Some(&build_value(3)));
+        assert_eq!(oof.sibling_value(3), Some(&build_value(2)));
+        assert_eq!(oof.sibling_value(6), None);
+        assert_eq!(oof.sibling_value(1), None);
+    }
+
+    #[test]
+    fn complete_pairs() {
+        let keys = [1, 2, 3, 6, 12, 13, 14];
+        let oof = Oof::new(&keys, &[build_value(0); 7]);
+
+        let pairs: Vec<_> = oof.complete_pairs().collect();
+        assert_eq!(pairs, [(1, 2, 3), (6, 12, 13)]);
+    }
+
+    #[test]
+    fn verify_layered() {
+        let child = Oof::new(&[2, 3], &[build_value(4), build_value(5)]);
+        let child_root = hash(&build_value(4), &build_value(5));
+
+        let root = hash(&child_root, &build_value(3));
+        let mut parent = Oof::new(&[3], &[build_value(3)]);
+
+        assert_eq!(
+            parent.clone().verify_layered(&[(2, child.clone())], &root),
+            Ok(true)
+        );
+        assert_eq!(
+            parent
+                .clone()
+                .verify_layered(&[(2, child.clone())], &build_value(0)),
+            Ok(false)
+        );
+
+        // No child is inserted unless every child has a root.
+        let partial = Oof::new(&[2], &[build_value(6)]);
+        let before = parent.clone();
+        assert_eq!(
+            parent.verify_layered(&[(2, child), (3, partial)], &root),
+            Err(Error::EntryNotFound(3))
+        );
+        assert_eq!(parent, before);
+    }
+
+    #[test]
+    fn prove() {
+        let keys: Vec<K> = (8..16).collect();
+        let values: Vec<V> = (8..16).map(build_value).collect();
+        let oof = Oof::new(&keys, &values);
+
+        let eight = oof.field_proof(8).unwrap();
+        let nine = oof.field_proof(9).unwrap();
+        let both = oof.prove(&[8, 9]).unwrap();
+
+        assert_eq!(both.keys(), [3, 5, 8, 9].iter().cloned().collect());
+        assert!(both.len() < eight.len() + nine.len());
+        assert_eq!(both.compute_root(), oof.compute_root());
+
+        let apart = oof.prove(&[8, 15]).unwrap();
+        assert_eq!(apart.keys(), [5, 6, 8, 9, 14, 15].iter().cloned().collect());
+        assert_eq!(apart.compute_root(), oof.compute_root());
+    }
+
+    #[test]
+    fn verify_multi() {
+        let keys: Vec<K> = (8..16).collect();
+        let values: Vec<V> = (8..16).map(build_value).collect();
+        let oof = Oof::new(&keys, &values);
+        let root = oof.compute_root().unwrap();
+
+        let proof = oof.prove(&[8, 13]).unwrap();
+        let entries = [(8, values[0]), (13, values[5])];
+
+        assert_eq!(proof.verify_multi(&entries, &root), Ok(true));
+        assert_eq!(proof.verify_multi(&entries, &build_value(0)), Ok(false));
+        assert_eq!(
+            proof.verify_multi(&[(8, values[0]), (13, values[4])], &root),
+            Ok(false)
+        );
+        assert_eq!(
+            proof.verify_multi(&[(10, values[2])], &root),
+            Err(Error::EntryNotFound(10))
+        );
+    }
+
+    #[test]
+    fn no_leaves() {
+        let root = hash(&build_value(2), &build_value(3));
+        let mut oof = Oof::new(&[1], &[root]);
+
+        assert!(!oof.has_leaves());
+        assert_eq!(oof.verify(&root), Ok(true));
+
+        oof.set_strict(true);
+        assert_eq!(oof.verify(&root), Err(Error::NoLeaves));
+        assert_eq!(oof.root(), Err(Error::NoLeaves));
+
+        let mut oof = Oof::new(&[1, 2, 3], &[root, build_value(2), build_value(3)]);
+        oof.set_strict(true);
+        assert!(oof.has_leaves());
+        assert_eq!(oof.verify(&root), Ok(true));
+    }
+
+    #[test]
+    fn depth_for_leaves() {
+        assert_eq!(super::depth_for_leaves(0), 0);
+        assert_eq!(super::depth_for_leaves(1), 0);
+        assert_eq!(super::depth_for_leaves(2), 1);
+        assert_eq!(super::depth_for_leaves(3), 2);
+        assert_eq!(super::depth_for_leaves(4), 2);
+        assert_eq!(super::depth_for_leaves(5), 3);
+        assert_eq!(super::depth_for_leaves(1 << 40), 40);
+        assert_eq!(super::depth_for_leaves((1 << 40) + 1), 41);
+        assert_eq!(super::depth_for_leaves(u64::MAX), 64);
+    }
+
+    #[test]
+    fn pack_and_merkleize() {
+        // Vector[uint64, 5] containing 1..=5.
+        let data: Vec<u8> = (1..=5u64).flat_map(|n| n.to_le_bytes().to_vec()).collect();
+
+        let root = super::pack_and_merkleize(&data, 1).unwrap();
+        assert_eq!(
+            root,
+            [
+                0xbf, 0x03, 0x3e, 0x82, 0x43, 0x5f, 0xc6, 0x91, 0x58, 0x33, 0xd0, 0xf0, 0x32, 0x5b,
+                0x9a, 0x75, 0x2b, 0x2b, 0xef, 0x67, 0x49, 0x3b, 0x9d, 0x27, 0x93, 0x9e, 0x9b, 0x2f,
+                0xef, 0x56, 0xa5, 0xa8
+            ]
+        );
+
+        let padded = super::pack_and_merkleize(&data, 2).unwrap();
+        assert_eq!(padded, hash(&root, &hash(&[0u8; 32], &[0u8; 32])));
+
+        assert_eq!(super::pack_and_merkleize(&[], 0), Ok([0u8; 32]));
+        assert_eq!(
+            super::pack_and_merkleize(&data, 0),
+            Err(Error::TooManyLeaves(2))
+        );
+    }
+
+    #[test]
+    fn bitlist_root() {
+        let bits = [true, false, true, true, false, false, false, true, true];
+        assert_eq!(
+            super::bitlist_root(&bits, 2048),
+            Ok(from_hex(
+                "0283a97f8046e1c55c9d2f7ab40ae9c3474c1ca6968dfb1a43790df6008b5355"
+            ))
+        );
+
+        assert_eq!(
+            super::bitlist_root(&[], 2048),
+            Ok(from_hex(
+                "e8e527e84f666163a90ef900e013f56b0a4d020148b2224057b719f351b003a6"
+            ))
+        );
+
+        assert_eq!(super::bitlist_root(&bits, 8), Err(Error::BitlistTooLong(9)));
+    }
+
+    #[test]
+    fn from_blob() {
+        let count: u32 = 3;
+
+        let keys: [K; 3] = [1, 2, 3];
+        let values: [V; 3] = [build_value(1), build_value(2), build_value(3)];
+
+        let keys: [u8; 48] = unsafe { transmute(keys) };
+        let values: [u8; 96] = unsafe { transmute(values) };
+
+        let mut blob = [0u8; (4 + 48 + 96)];
+        blob[0..4].copy_from_slice(&count.to_le_bytes());
+        blob[4..52].copy_from_slice(&keys[..]);
+        blob[52..148].copy_from_slice(&values[..]);
+
+        let oof = unsafe { Oof::from_raw(blob[..].as_ptr() as *mut u8) };
+
+        assert_eq!(oof.get(&1), Some(&build_value(1)));
+        assert_eq!(oof.get(&2), Some(&build_value(2)));
+        assert_eq!(oof.get(&3), Some(&build_value(3)));
+        assert_eq!(oof.get(&4), None);
+    }
+
+    #[test]
+    fn try_from_slice() {
+        let oof = Oof::new(&[2, 3], &[build_value(2), build_value(3)]);
+        let bytes = oof.to_bytes();
+
+        assert_eq!(Oof::try_from(&bytes[..]), Ok(oof));
+        assert_eq!(
+            Oof::try_from(&bytes[..2]),
+            Err(Error::MalformedInput {
+                offset: 0,
+                expected: 4,
+                actual: 2
+            })
+        );
+        assert_eq!(
+            Oof::try_from(&bytes[..40]),
+            Err(Error::MalformedInput {
+                offset: 36,
+                expected: 64,
+                actual: 4
+            })
+        );
+    }
+
+    #[test]
+    fn trailing_data() {
+        let oof = Oof::new(&[2, 3], &[build_value(2), build_value(3)]);
+        let mut bytes = oof.to_bytes();
+        bytes.extend(oof.to_bytes());
+
+        assert_eq!(Oof::from_bytes(&bytes), Err(Error::TrailingData(100)));
+    }
+
+    #[test]
+    fn from_bytes_minimal() {
+        let values = [build_value(2), build_value(6), build_value(7)];
+        let minimal = Oof::new(&[2, 6, 7], &values);
+        assert_eq!(
+            Oof::from_bytes_minimal(&minimal.to_bytes()),
+            Ok(minimal.clone())
+        );
+
+        let mut redundant = minimal.clone();
+        redundant.root().unwrap();
+        assert_eq!(
+            Oof::from_bytes_minimal(&redundant.to_bytes()),
+            Err(Error::RedundantNode(1))
+        );
+
+        let three = Oof::new(&[2, 3, 6, 7], &[values[0], values[1], values[1], values[2]]);
+        assert_eq!(
+            Oof::from_bytes_minimal(&three.to_bytes()),
+            Err(Error::RedundantNode(3))
+        );
+    }
+
+    #[test]
+    fn prune() {
+        let values = [build_value(2), build_value(6), build_value(7)];
+        let minimal = Oof::new(&[2, 6, 7], &values);
+
+        let mut full = minimal.clone();
+        full.root().unwrap();
+        full.prune();
+        assert_eq!(full, minimal);
+
+        // 1 is only derivable through 3, which itself isn't present.
+        let mut indirect = Oof::new(
+            &[1, 2, 6, 7],
+            &[build_value(1), values[0], values[1], values[2]],
+        );
+        indirect.prune();
+        assert_eq!(indirect, minimal);
+    }
+
+    #[test]
+    fn compact_bytes() {
+        let mut full = Oof::new(
+            &[2, 6, 7],
+            &[build_value(2), build_value(6), build_value(7)],
+        );
+        full.root().unwrap();
+
+        let compact = full.to_compact_bytes();
+        assert!(compact.len() < full.to_bytes().len());
+
+        let restored = Oof::from_compact_bytes(&compact).unwrap();
+        assert_eq!(restored.equivalent(&full), Ok(true));
+        assert_eq!(restored.keys(), [2, 6, 7].iter().cloned().collect());
+    }
+
+    #[test]
+    fn big_endian_round_trip() {
+        let oof = Oof::new(&[2, 0x0102, 0x0103], &[build_value(1); 3]);
+
+        let little = oof.to_bytes();
+        let big = oof.to_bytes_with(Endianness::Big);
+
+        assert_ne!(little, big);
+        assert_eq!(Oof::from_bytes(&little), Ok(oof.clone()));
+        assert_eq!(Oof::from_bytes_with(&big, Endianness::Big), Ok(oof));
+        assert_eq!(
+            Oof::from_bytes(&big[0..60]),
+            Err(Error::MalformedInput {
+                offset: 52,
+                expected: 96,
+                actual: 8
+            })
+        );
+    }
+
+    #[test]
+    fn oversized_count() {
+        let bytes = (1u32 << 28).to_le_bytes();
+
+        assert_eq!(
+            Oof::from_bytes(&bytes),
+            Err(Error::MalformedInput {
+                offset: 4,
+                expected: (1 << 28) * size_of::<K>(),
+                actual: 0
+            })
+        );
+        assert_eq!(
+            region(&bytes, 2, usize::MAX),
+            Err(Error::MalformedInput {
+                offset: 2,
+                expected: usize::MAX,
+                actual: 2
+            })
+        );
+    }
+
+    #[test]
+    fn short_value_region() {
+        let oof = Oof::new(&[2, 6, 7], &[build_value(1); 3]);
+        let bytes = oof.to_bytes();
+
+        assert_eq!(
+            Oof::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(Error::MalformedInput {
+                offset: 52,
+                expected: 96,
+                actual: 95
+            })
+        );
+    }
+
+    #[test]
+    fn missing_nodes() {
+        let keys: Vec<K> = (16..32).collect();
+        let values: Vec<V> = (16..32).map(build_value).collect();
+        let mut full = Oof::new(&keys, &values);
+        full.refresh().unwrap();
+
+        let mut partial = full.prove(&[18, 29]).unwrap();
+        let removed: BTreeSet<K> = [5, 28].iter().cloned().collect();
+        for key in removed.iter() {
+            partial.map.remove(key);
+        }
+
+        let missing = partial.missing_nodes();
+        assert_eq!(missing, removed);
+
+        for key in missing {
+            partial.set(key, *full.get_required(&key).unwrap());
+        }
+        assert_eq!(partial.root(), full.compute_root());
+        assert!(partial.missing_nodes().is_empty());
+
+        assert_eq!(Oof::empty().missing_nodes(), [1].iter().cloned().collect());
+    }
+
+    #[test]
+    fn with_hasher() {
+        let keys: Vec<K> = (4..8).collect();
+        let values: Vec<V> = (4..8).map(build_value).collect();
+
+        let sha = Oof::with_hasher(&keys, &values, HashAlg::Sha256);
+        let keccak = Oof::with_hasher(&keys, &values, HashAlg::Keccak256);
+
+        assert_eq!(sha.compute_root(), Oof::new(&keys, &values).compute_root());
+        assert_ne!(sha.compute_root(), keccak.compute_root());
+
+        let two = HashAlg::Keccak256.hash(&values[0], &values[1]);
+        let three = HashAlg::Keccak256.hash(&values[2], &values[3]);
+        let expected = HashAlg::Keccak256.hash(&two, &three);
+        assert_eq!(keccak.compute_root(), Ok(expected));
+
+        let mut scratch = Map::new();
+        assert_eq!(keccak.compute_root_into(&mut scratch), Ok(expected));
+    }
+
+    #[test]
+    fn is_subproof_of() {
+        let big = Oof::new(
+            &[2, 6, 7],
+            &[build_value(2), build_value(6), build_value(7)],
+        );
+        let small = Oof::new(&[2, 7], &[build_value(2), build_value(7)]);
+
+        assert!(small.is_subproof_of(&big));
+        assert!(big.is_subproof_of(&big));
+        assert!(Oof::empty().is_subproof_of(&big));
+        assert!(!big.is_subproof_of(&small));
+
+        let conflicting = Oof::new(&[2, 7], &[build_value(2), build_value(8)]);
+        assert!(!conflicting.is_subproof_of(&big));
+    }
+
+    #[test]
+    fn refresh_until() {
+        let keys: Vec<K> = (8..16).collect();
+        let values: Vec<V> = (8..16).map(build_value).collect();
+        let mut oof = Oof::new(&keys, &values);
+
+        let expected = hash(&values[0], &values[1]);
+        assert_eq!(oof.refresh_until(4), Ok(&expected));
+        assert!(oof.get(&3).is_none());
+        assert!(oof.get(&2).is_none());
+
+        let mut full = oof.clone();
+        full.refresh().unwrap();
+        assert_eq!(oof.refresh_until(3), Ok(full.get(&3).unwrap()));
+
+        let mut partial = Oof::new(&[8, 10, 11], &[build_value(8); 3]);
+        assert_eq!(partial.refresh_until(2), Err(Error::EntryNotFound(9)));
+        assert_eq!(
+            partial.refresh_until(5).copied(),
+            Ok(hash(&build_value(8), &build_value(8)))
+        );
+    }
+
+    #[test]
+    fn refresh_until_padded() {
+        let mut oof = Oof::new(&[8], &[build_value(8)]);
+        oof.set_zero_padding(3);
+
+        let zero = ZERO_HASHES[2];
+        assert_eq!(oof.refresh_until(3), Ok(&zero));
+        assert_eq!(oof.compute_root(), {
+            let mut full = Oof::new(&[8], &[build_value(8)]);
+            full.set_zero_padding(3);
+            full.compute_root()
+        });
+    }
+
+    #[test]
+    fn refresh_until_evicts_stale_parent() {
+        let mut oof = Oof::new(
+            &[1, 2, 6, 7],
+            &[
+                build_value(1),
+                build_value(2),
+                build_value(6),
+                build_value(7),
+            ],
+        );
+        let root = oof.compute_root();
+
+        assert_eq!(
+            oof.refresh_until(3).copied(),
+            Ok(hash(&build_value(6), &build_value(7)))
+        );
+        assert_eq!(oof.get(&1), None);
+        assert_eq!(oof.compute_root(), root);
+    }
+
+    #[test]
+    fn same_root_as() {
+        let leaves = Oof::new(
+            &[2, 6, 7],
+            &[build_value(2), build_value(6), build_value(7)],
+        );
+        let three = hash(&build_value(6), &build_value(7));
+        let internal = Oof::new(&[2, 3], &[build_value(2), three]);
+        let other = Oof::new(&[2, 3], &[build_value(2), build_value(3)]);
+
+        assert_eq!(leaves.same_root_as(&internal), Ok(true));
+        assert_eq!(leaves.same_root_as(&other), Ok(false));
+        assert_eq!(leaves.same_root_as(&Oof::empty()), Err(Error::EmptyProof));
+    }
+
+    #[test]
+    fn indexed_leaves() {
+        let oof = Oof::new(
+            &[3, 5, 8, 9, 11],
+            &[
+                build_value(3),
+                build_value(5),
+                build_value(8),
+                build_value(9),
+                build_value(11),
+            ],
+        );
+
+        assert_eq!(
+            oof.indexed_leaves(),
+            vec![
+                (0, build_value(8)),
+                (1, build_value(9)),
+                (3, build_value(11))
+            ]
+        );
+        assert!(Oof::empty().indexed_leaves().is_empty());
+    }
+
+    #[test]
+    fn verification_cost() {
+        let keys: Vec<K> = (8..16).collect();
+        let values: Vec<V> = (8..16).map(build_value).collect();
+        let mut oof = Oof::new(&keys, &values);
+        assert_eq!(oof.verification_cost(), 7);
+
+        let minimal = oof.prove(&[8, 15]).unwrap();
+        assert_eq!(minimal.verification_cost(), 5);
+
+        let mut counted = 0;
+        oof.refresh_with_progress(|_| counted += 1).unwrap();
+        assert_eq!(counted, 7);
+        assert_eq!(oof.verification_cost(), 0);
+
+        // 1 is stale once 2 is derived beneath it.
+        let stale = Oof::new(&[1, 3, 4, 5], &[build_value(1); 4]);
+        assert_eq!(stale.verification_cost(), 2);
+        let mut counted = 0;
+        stale
+            .clone()
+            .refresh_with_progress(|_| counted += 1)
+            .unwrap();
+        assert_eq!(counted, 2);
+
+        oof.set_domain(build_value(1));
+        assert_eq!(oof.verification_cost(), 1);
+    }
+
+    #[test]
+    fn set_subtree_root() {
+        let mut child = Oof::new(&[2, 3], &[build_value(4), build_value(5)]);
+        let child_root = hash(&build_value(4), &build_value(5));
+
+        let mut parent = Oof::new(&[4, 5, 3], &[build_value(0); 3]);
+        parent.refresh().unwrap();
+
+        parent.set_subtree_root(5, &mut child).unwrap();
+        assert_eq!(parent.get(&5), Some(&child_root));
+        assert!(parent.get(&2).is_none());
+        assert!(parent.get(&1).is_none());
+
+        let two = hash(&build_value(0), &child_root);
+        assert_eq!(parent.root(), Ok(hash(&two, &build_value(0))));
+
+        assert_eq!(
+            parent.set_subtree_root(2, &mut Oof::empty()),
+            Err(Error::EmptyProof)
+        );
+        assert_eq!(
+            parent.set_subtree_root(0, &mut child),
+            Err(Error::InvalidIndex(0))
+        );
+    }
+
+    #[test]
+    fn contains_complete_subtree() {
+        let keys: Vec<K> = (8..16).filter(|k| *k != 13).collect();
+        let oof = Oof::new(&keys, &[build_value(0); 7]);
+
+        assert!(oof.contains_complete_subtree(2, 2));
+        assert!(oof.contains_complete_subtree(4, 1));
+        assert!(oof.contains_complete_subtree(12, 0));
+        assert!(!oof.contains_complete_subtree(3, 2));
+        assert!(!oof.contains_complete_subtree(6, 1));
+        assert!(!oof.contains_complete_subtree(1, 3));
+        assert!(!oof.contains_complete_subtree(0, 1));
+        assert!(!oof.contains_complete_subtree(2, 127));
+    }
+
+    #[test]
+    fn rebase() {
+        let mut oof = Oof::new(
+            &[2, 6, 7],
+            &[build_value(2), build_value(6), build_value(7)],
+        );
+        oof.refresh().unwrap();
+
+        let rebased = oof.rebase(4).unwrap();
+        assert_eq!(rebased.keys(), [4, 8, 9, 18, 19].iter().cloned().collect());
+        assert_eq!(rebased.get(&4), oof.get(&1));
+        assert_eq!(rebased.get(&19), Some(&build_value(7)));
+
+        assert_eq!(oof.rebase(0), Err(Error::InvalidIndex(0)));
+        assert_eq!(oof.rebase(1), Ok(oof.clone()));
+    }
+
+    #[test]
+    fn from_framed() {
+        let first = Oof::new(&[2, 3], &[build_value(2), build_value(3)]);
+        let second = Oof::new(&[1], &[build_value(1)]);
+
+        let mut data = Vec::new();
+        for oof in [&first, &second].iter() {
+            let bytes = oof.to_bytes();
+            data.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            data.extend(bytes);
+        }
+
+        let (oof, used) = Oof::from_framed(&data).unwrap();
+        assert_eq!(oof, first);
+        let (oof, rest) = Oof::from_framed(&data[used..]).unwrap();
+        assert_eq!(oof, second);
+        assert_eq!(used + rest, data.len());
+
+        assert_eq!(
+            Oof::from_framed(&data[..used - 1]),
+            Err(Error::MalformedInput {
+                offset: 4,
+                expected: used - 4,
+                actual: used - 5
+            })
+        );
+    }
+
+    #[test]
+    fn root_verbose() {
+        let keys: Vec<K> = (8..16).collect();
+        let values: Vec<V> = (8..16).map(build_value).collect();
+        let oof = Oof::new(&keys, &values);
+        let mut tagged = oof.prove(&[8, 13]).unwrap();
+        tagged.set_domain(build_value(1));
+
+        let mut refreshed = tagged.clone();
+        refreshed.refresh().unwrap();
+
+        let (root, nodes) = tagged.clone().root_verbose().unwrap();
+        assert_eq!(root, tagged.compute_root().unwrap());
+        assert_eq!(nodes.keys().cloned().collect::<Vec<_>>(), [1, 2, 3, 4, 6]);
+
+        let mut merged = tagged.clone();
+        merged.map.extend(nodes);
+        assert_eq!(merged, refreshed);
+
+        let mut padded = Oof::new(&[8, 13], &[build_value(8), build_value(13)]);
+        padded.set_zero_padding(3);
+        let mut refreshed = padded.clone();
+        refreshed.refresh().unwrap();
+
+        let (_, nodes) = padded.clone().root_verbose().unwrap();
+        assert!(nodes.contains_key(&9));
+        let mut merged = padded;
+        merged.map.extend(nodes);
+        assert_eq!(merged, refreshed);
+
+        assert_eq!(Oof::empty().root_verbose(), Err(Error::EmptyProof));
+    }
+
+    #[test]
+    fn index() {
+        let oof = Oof::new(&[2, 6], &[build_value(2), build_value(6)]);
+        assert_eq!(oof[6], build_value(6));
+        assert_eq!(oof[2], build_value(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "no entry for generalized index 7")]
+    fn index_missing() {
+        let oof = Oof::new(&[2, 6], &[build_value(2), build_value(6)]);
+        let _ = oof[7];
+    }
+
+    #[test]
+    fn root_from_fn() {
+        for depth in 0..5 {
+            for count in 1..=1u64 << depth {
+                let leaves: Vec<V> = (0..count).map(|i| build_value(i as u8 + 1)).collect();
+                let expected = Oof::from_leaves(&leaves, depth).unwrap().root();
+
+                let root = super::root_from_fn(depth, count, |i| leaves[i as usize]);
+                assert_eq!(root, expected);
+            }
+        }
+
+        assert_eq!(
+            super::root_from_fn(40, 0, |_| unreachable!()),
+            Ok(ZERO_HASHES[40])
+        );
+        assert_eq!(
+            super::root_from_fn(2, 5, |i| build_value(i as u8)),
+            Err(Error::TooManyLeaves(5))
+        );
+        assert_eq!(
+            super::root_from_fn(2, u64::MAX, |i| build_value(i as u8)),
+            Err(Error::TooManyLeaves(usize::MAX))
+        );
+    }
+
+    #[test]
+    fn compress_zero_runs() {
+        let full = Oof::from_leaves(&[build_value(1), build_value(2), build_value(3)], 4).unwrap();
+        let mut proof = full.prove(&[16, 18]).unwrap();
+        let root = proof.compute_root();
+        assert_eq!(
+            proof.keys(),
+            [3, 5, 16, 17, 18, 19].iter().cloned().collect()
+        );
+
+        proof.compress_zero_runs();
+        assert_eq!(proof.keys(), [16, 17, 18].iter().cloned().collect());
+        assert_eq!(proof.compute_root(), root);
+
+        let mut empty = Oof::from_leaves(&[[0u8; 32]], 3).unwrap();
+        let root = empty.compute_root();
+        empty.compress_zero_runs();
+        assert_eq!(empty.keys(), [8].iter().cloned().collect());
+        assert_eq!(empty.compute_root(), root);
+
+        let mut unpadded = Oof::new(&[2, 3], &[[0u8; 32]; 2]);
+        unpadded.compress_zero_runs();
+        assert_eq!(unpadded.len(), 2);
+    }
+
+    #[test]
+    fn root_and_minimality() {
+        let mut minimal = Oof::new(
+            &[2, 6, 7],
+            &[build_value(2), build_value(6), build_value(7)],
+        );
+        let root = minimal.compute_root().unwrap();
+        assert_eq!(minimal.root_and_minimality(), Ok((root, true)));
+
+        // The call above filled in 3 and 1, so the proof is now bloated.
+        assert_eq!(minimal.root_and_minimality(), Ok((root, false)));
+
+        let three = hash(&build_value(6), &build_value(7));
+        let mut bloated = Oof::new(
+            &[2, 3, 6, 7],
+            &[build_value(2), three, build_value(6), build_value(7)],
+        );
+        assert_eq!(bloated.root_and_minimality(), Ok((root, false)));
+    }
+
+    #[test]
+    fn try_from_raw() {
+        let oof = Oof::new(
+            &[1, 2, 3],
+            &[build_value(1), build_value(2), build_value(3)],
+        );
+        let mut blob = oof.to_bytes();
+        let len = blob.len();
+        blob.extend_from_slice(&[0xff; 8]);
+
+        assert_eq!(
+            unsafe { Oof::try_from_raw(blob.as_ptr(), len) },
+            Ok(oof.clone())
+        );
+        assert_eq!(
+            unsafe { Oof::try_from_raw(blob.as_ptr(), blob.len()) },
+            Ok(oof)
+        );
+
+        assert_eq!(
+            unsafe { Oof::try_from_raw(blob.as_ptr(), len - 1) },
+            Err(Error::MalformedInput {
+                offset: 52,
+                expected: 96,
+                actual: 95
+            })
+        );
+        assert_eq!(
+            unsafe { Oof::try_from_raw(blob.as_ptr(), 2) },
+            Err(Error::MalformedInput {
+                offset: 0,
+                expected: 4,
+                actual: 2
+            })
+        );
+
+        blob[0..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            unsafe { Oof::try_from_raw(blob.as_ptr(), blob.len()) },
+            Err(Error::MalformedInput { offset: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn verify_sibling_pair() {
+        let mut oof = Oof::new(
+            &[2, 6, 7],
+            &[build_value(2), build_value(6), build_value(7)],
+        );
+        let three = hash(&build_value(6), &build_value(7));
+
+        assert!(oof.are_siblings(6, 7));
+        assert!(oof.are_siblings(7, 6));
+        assert!(!oof.are_siblings(2, 6));
+        assert!(!oof.are_siblings(1, 0));
+
+        assert_eq!(oof.verify_sibling_pair(7, 6), Ok(three));
+        assert_eq!(oof.get(&3), Some(&three));
+        assert_eq!(oof.verify_sibling_pair(2, 6), Err(Error::InvalidIndex(6)));
+        assert_eq!(
+            oof.verify_sibling_pair(2, 3),
+            Ok(hash(&build_value(2), &three))
+        );
+        assert_eq!(oof.verify_sibling_pair(4, 5), Err(Error::EntryNotFound(4)));
+
+        oof.map.insert(3, build_value(3));
+        assert_eq!(oof.verify_sibling_pair(6, 7), Err(Error::Conflict(3)));
+        assert_eq!(oof.get(&3), Some(&build_value(3)));
+    }
+
+    #[test]
+    fn verify_sibling_pair_evicts_stale_parent() {
+        let mut oof = Oof::new(
+            &[1, 2, 6, 7],
+            &[
+                build_value(1),
+                build_value(2),
+                build_value(6),
+                build_value(7),
+            ],
+        );
+        let root = oof.compute_root();
+
+        assert!(oof.verify_sibling_pair(6, 7).is_ok());
+        assert_eq!(oof.compute_root(), root);
+        assert_eq!(oof.get(&1), None);
+    }
+
+    #[test]
+    fn entries() {
+        let oof = Oof::new(
+            &[7, 2, 6],
+            &[build_value(7), build_value(2), build_value(6)],
+        );
+        let entries = oof.to_entries();
+
+        assert_eq!(
+            entries,
+            vec![
+                (2, build_value(2)),
+                (6, build_value(6)),
+                (7, build_value(7))
+            ]
+        );
+        assert_eq!(Oof::from_entries(entries), Ok(oof));
+        assert_eq!(Oof::from_entries(Vec::new()), Ok(Oof::empty()));
+
+        assert_eq!(
+            Oof::from_entries(vec![
+                (2, build_value(2)),
+                (7, build_value(7)),
+                (6, build_value(6))
+            ]),
+            Err(Error::Unsorted(6))
+        );
+        assert_eq!(
+            Oof::from_entries(vec![(2, build_value(2)), (2, build_value(2))]),
+            Err(Error::DuplicateKey(2))
+        );
+    }
+
+    #[test]
+    fn set_and_root() {
+        let keys: Vec<K> = (8..16).collect();
+        let values: Vec<V> = (8..16).map(build_value).collect();
+        let mut oof = Oof::new(&keys, &values);
+        oof.refresh().unwrap();
+
+        let root = oof.set_and_root(13, build_value(42)).unwrap();
+
+        let mut values = values;
+        values[5] = build_value(42);
+        let rebuilt = Oof::new(&keys, &values);
+        assert_eq!(Ok(root), rebuilt.compute_root());
+        assert_eq!(oof.compute_root(), Ok(root));
+
+        // 7 is missing, so the path can't be walked and `root` reports it.
+        let mut sparse = Oof::new(&[2, 6], &[build_value(2), build_value(6)]);
+        assert_eq!(
+            sparse.set_and_root(6, build_value(1)),
+            Err(Error::EntryNotFound(7))
+        );
+
+        let mut padded = Oof::from_leaves(&[build_value(1)], 3).unwrap();
+        let root = padded.set_and_root(9, build_value(2)).unwrap();
+        let expected = Oof::from_leaves(&[build_value(1), build_value(2)], 3).unwrap();
+        assert_eq!(expected.compute_root(), Ok(root));
+    }
+
+    #[test]
+    fn set_and_root_unchanged_on_error() {
+        let mut strict = Oof::new(&[1], &[build_value(1)]);
+        strict.set_strict(true);
+        let before = strict.clone();
+        assert_eq!(strict.set_and_root(1, build_value(2)), Err(Error::NoLeaves));
+        assert_eq!(strict, before);
+
+        let mut sparse = Oof::new(
+            &[1, 2, 6],
+            &[build_value(1), build_value(2), build_value(6)],
+        );
+        let before = sparse.clone();
+        assert_eq!(
+            sparse.set_and_root(6, build_value(3)),
+            Err(Error::EntryNotFound(7))
+        );
+        assert_eq!(sparse, before);
+    }
+
+    #[test]
+    fn validate() {
+        let mut oof = Oof::new(
+            &[2, 6, 7],
+            &[build_value(2), build_value(6), build_value(7)],
+        );
+        assert_eq!(oof.validate(), Ok(()));
+        oof.refresh().unwrap();
+        assert_eq!(oof.validate(), Ok(()));
+
+        let mut zero = oof.clone();
+        zero.map.insert(0, build_value(0));
+        assert_eq!(zero.validate(), Err(Error::InvalidIndex(0)));
+
+        let mut disconnected = oof.clone();
+        disconnected.map.insert(10, build_value(10));
+        assert_eq!(disconnected.validate(), Err(Error::EntryNotFound(11)));
+        assert_eq!(Oof::empty().validate(), Err(Error::EmptyProof));
+
+        let mut inconsistent = oof;
+        inconsistent.map.insert(3, build_value(3));
+        assert_eq!(inconsistent.validate(), Err(Error::Conflict(3)));
+    }
+
+    #[test]
+    fn combine_roots() {
+        let (four, five) = (build_value(4), build_value(5));
+
+        assert_eq!(
+            Oof::combine_roots(4, four, five),
+            Ok((2, hash(&four, &five)))
+        );
+        assert_eq!(
+            Oof::combine_roots(5, four, five),
+            Err(Error::InvalidIndex(5))
+        );
+        assert_eq!(
+            Oof::combine_roots(0, four, five),
+            Err(Error::InvalidIndex(0))
+        );
+    }
+
+    #[test]
+    fn heap_size() {
+        let build = |n: K| {
+            let keys: Vec<K> = (n..2 * n).collect();
+            Oof::new(&keys, &vec![build_value(0); n as usize])
+        };
+
+        assert_eq!(Oof::empty().heap_size(), 0);
+
+        let small = build(1 << 8).heap_size();
+        let large = build(1 << 12).heap_size();
+        assert!(small >= 256 * (size_of::<K>() + size_of::<V>()));
+
+        let ratio = large as f64 / small as f64;
+        assert!((15.0..17.0).contains(&ratio));
+    }
+
+    #[test]
+    fn derivation_order() {
+        let keys: Vec<K> = (16..32).collect();
+        let values: Vec<V> = (16..32).map(build_value).collect();
+        let oof = Oof::new(&keys, &values);
+
+        let order = oof.derivation_order();
+        assert_eq!(order, (1..16).rev().collect::<Vec<_>>());
+        assert!(order.windows(2).all(|pair| pair[0] > pair[1]));
+
+        let proof = oof.prove(&[18, 29]).unwrap();
+        assert_eq!(proof.derivation_order(), [14, 9, 7, 4, 3, 2, 1]);
+        assert_eq!(proof.derivation_order(), {
+            let mut keys = Vec::new();
+            proof
+                .clone()
+                .refresh_with_progress(|key| keys.push(key))
+                .unwrap();
+            keys
+        });
+        assert_eq!(oof.keys(), keys.iter().cloned().collect());
+
+        // Stops where refresh fails, at the missing sibling of 6.
+        let mut partial = Oof::new(&[2, 12, 13], &[build_value(1); 3]);
+        assert_eq!(partial.derivation_order(), [6]);
+        let mut hashes = Vec::new();
+        assert_eq!(
+            partial.refresh_with_progress(|key| hashes.push(key)),
+            Err(Error::EntryNotFound(7))
+        );
+        assert_eq!(hashes, [6]);
+
+        let mut padded = Oof::new(&[8], &[build_value(8)]);
+        padded.set_zero_padding(3);
+        assert_eq!(padded.derivation_order(), [4, 2, 1]);
+    }
+
+    #[test]
+    fn leaves_digest() {
+        let oof = Oof::new(
+            &[2, 6, 7],
+            &[build_value(2), build_value(6), build_value(7)],
+        );
+        let reordered = Oof::new(
+            &[7, 2, 6],
+            &[build_value(7), build_value(2), build_value(6)],
+        );
+        assert_eq!(oof.leaves_digest(), reordered.leaves_digest());
+
+        let mut refreshed = oof.clone();
+        refreshed.refresh().unwrap();
+        assert_eq!(refreshed.leaves_digest(), oof.leaves_digest());
+
+        let changed = Oof::new(
+            &[2, 6, 7],
+            &[build_value(2), build_value(6), build_value(8)],
+        );
+        assert_ne!(changed.leaves_digest(), oof.leaves_digest());
+
+        let moved = Oof::new(
+            &[3, 4, 5],
+            &[build_value(2), build_value(6), build_value(7)],
+        );
+        assert_ne!(moved.leaves_digest(), oof.leaves_digest());
+
+        let root_only = Oof::new(&[1], &[build_value(1)]);
+        let other_root = Oof::new(&[1], &[build_value(2)]);
+        assert_ne!(root_only.leaves_digest(), other_root.leaves_digest());
+    }
+
+    #[test]
+    fn refresh_collect_errors() {
+        let keys: Vec<K> = (8..16).filter(|k| *k != 9 && *k != 14).collect();
+        let values: Vec<V> = keys.iter().map(|k| build_value(*k as u8)).collect();
+        let mut oof = Oof::new(&keys, &values);
+
+        assert_eq!(
+            oof.refresh_collect_errors(),
+            Err(vec![Error::EntryNotFound(14), Error::EntryNotFound(9)])
+        );
+        assert!(oof.get(&5).is_some());
+        assert!(oof.get(&6).is_some());
+        assert!(oof.get(&1).is_none());
+
+        oof.set(9, build_value(9));
+        oof.set(14, build_value(14));
+        assert_eq!(oof.refresh_collect_errors(), Ok(()));
+
+        let all: Vec<K> = (8..16).collect();
+        let full = Oof::new(
+            &all,
+            &all.iter()
+                .map(|k| build_value(*k as u8))
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(oof.get(&1).copied(), full.compute_root().ok());
     }
 }
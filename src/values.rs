@@ -0,0 +1,45 @@
+use crate::V;
+
+use arrayref::array_ref;
+
+/// Interprets `v` as a little-endian `u64`, as SSZ encodes basic integers.
+/// Panics if any byte past the low eight is non-zero.
+pub fn value_as_u64(v: &V) -> u64 {
+    assert!(
+        v[8..].iter().all(|b| *b == 0),
+        "value does not fit in a u64"
+    );
+
+    u64::from_le_bytes(*array_ref![v, 0, 8])
+}
+
+/// Encodes `n` little-endian into a zeroed chunk.
+pub fn u64_as_value(n: u64) -> V {
+    let mut v = [0u8; 32];
+    v[0..8].copy_from_slice(&n.to_le_bytes());
+    v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for n in [0, 1, 255, 256, 0xdead_beef, u64::MAX].iter() {
+            assert_eq!(value_as_u64(&u64_as_value(*n)), *n);
+        }
+
+        let v = u64_as_value(0x0102);
+        assert_eq!(v[0], 0x02);
+        assert_eq!(v[1], 0x01);
+    }
+
+    #[test]
+    #[should_panic(expected = "value does not fit in a u64")]
+    fn value_as_u64_high_bytes() {
+        let mut v = [0u8; 32];
+        v[8] = 1;
+        value_as_u64(&v);
+    }
+}
@@ -0,0 +1,161 @@
+use crate::{depth, zero_table, Error, Oof, K, V};
+
+use alloc::borrow::Cow;
+use alloc::collections::BinaryHeap;
+use bonsai::expand;
+
+/// Outcome of a single `Verifier::step`.
+#[derive(Debug, PartialEq)]
+pub enum StepResult {
+    InProgress,
+    Done(V),
+    Failed(Error),
+}
+
+/// Derives the root of a proof one hash at a time, so callers can bound the
+/// work done per invocation.
+#[derive(Clone, Debug)]
+pub struct Verifier {
+    oof: Oof,
+    keys: BinaryHeap<K>,
+    zeros: Cow<'static, [V]>,
+}
+
+impl Verifier {
+    pub fn new(oof: Oof) -> Self {
+        let keys = oof.keys_iter().collect();
+        let zeros = zero_table(oof.hasher, oof.padding.unwrap_or(0));
+        Self { oof, keys, zeros }
+    }
+
+    pub fn step(&mut self) -> StepResult {
+        if self.oof.is_empty() {
+            return StepResult::Failed(Error::EmptyProof);
+        }
+
+        if self.oof.strict && !self.oof.has_leaves() {
+            return StepResult::Failed(Error::NoLeaves);
+        }
+
+        while let Some(key) = self.keys.pop() {
+            if key <= 1 {
+                self.keys.clear();
+                break;
+            }
+
+            let (left, right, parent) = expand(key);
+
+            for index in [left, right].iter() {
+                if !self.oof.map.contains_key(index) {
+                    if let Some(value) = self.zero(*index) {
+                        self.oof.map.insert(*index, value);
+                    }
+                }
+            }
+
+            match (
+                self.oof.get(&left),
+                self.oof.get(&right),
+                self.oof.get(&parent),
+            ) {
+                (Some(l), Some(r), None) => {
+                    let h = self.oof.hasher.hash(l, r);
+                    self.oof.set(parent, h);
+                    self.keys.push(parent);
+                    return StepResult::InProgress;
+                }
+                (Some(_), Some(_), Some(_)) => (),
+                (None, _, _) => return self.fail(Error::EntryNotFound(left)),
+                (_, None, _) => return self.fail(Error::EntryNotFound(right)),
+            };
+        }
+
+        match (self.oof.get(&1), &self.oof.domain) {
+            (Some(root), Some(domain)) => StepResult::Done(self.oof.hasher.hash(root, domain)),
+            (Some(root), None) => StepResult::Done(*root),
+            (None, _) => StepResult::Failed(Error::EntryNotFound(1)),
+        }
+    }
+
+    /// Returns the zero subtree root standing in for an absent `key` under
+    /// zero padding.
+    fn zero(&self, key: K) -> Option<V> {
+        let height = self.oof.padding?.checked_sub(depth(key))?;
+        self.zeros.get(height as usize).copied()
+    }
+
+    fn fail(&mut self, error: Error) -> StepResult {
+        self.keys.clear();
+        StepResult::Failed(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_value(n: u8) -> V {
+        let mut tmp = [0u8; 32];
+        tmp[0] = n;
+        tmp
+    }
+
+    #[test]
+    fn step_to_completion() {
+        let keys = [2, 6, 14, 15];
+        let values = [
+            build_value(2),
+            build_value(6),
+            build_value(14),
+            build_value(15),
+        ];
+        let mut oof = Oof::new(&keys, &values);
+
+        let mut verifier = Verifier::new(oof.clone());
+        let mut steps = 0;
+
+        let root = loop {
+            match verifier.step() {
+                StepResult::InProgress => steps += 1,
+                StepResult::Done(root) => break root,
+                StepResult::Failed(e) => panic!("unexpected failure: {:?}", e),
+            }
+        };
+
+        assert_eq!(steps, 3);
+        assert_eq!(oof.root(), Ok(root));
+        assert_eq!(verifier.step(), StepResult::Done(root));
+    }
+
+    #[test]
+    fn step_with_padding() {
+        let mut oof = Oof::new(&[4, 5], &[build_value(4), build_value(5)]);
+        oof.set_zero_padding(2);
+
+        let full = Oof::from_leaves(&[build_value(1), build_value(2), build_value(3)], 4).unwrap();
+        let mut compressed = full.prove(&[16, 18]).unwrap();
+        compressed.compress_zero_runs();
+
+        for mut oof in [oof, compressed].iter().cloned() {
+            let mut verifier = Verifier::new(oof.clone());
+
+            let root = loop {
+                match verifier.step() {
+                    StepResult::InProgress => (),
+                    StepResult::Done(root) => break root,
+                    StepResult::Failed(e) => panic!("unexpected failure: {:?}", e),
+                }
+            };
+
+            assert_eq!(oof.root(), Ok(root));
+        }
+    }
+
+    #[test]
+    fn step_missing_sibling() {
+        let oof = Oof::new(&[2, 6], &[build_value(2), build_value(6)]);
+        let mut verifier = Verifier::new(oof);
+
+        assert_eq!(verifier.step(), StepResult::Failed(Error::EntryNotFound(7)));
+    }
+}